@@ -0,0 +1,116 @@
+use std::{collections::VecDeque, hint::black_box};
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+use trails::{
+    config::GenerationConfig,
+    generate,
+    map::{Map, Position},
+};
+
+const TRAIL_LENGTH: usize = 10;
+
+/// Densely packed map to search, as generated from a fixed seed
+fn dense_map(size: usize) -> Map {
+    let config = GenerationConfig::builder("benchmark")
+        .canvas_size(size)
+        .density(6)
+        .build()
+        .expect("Invalid config");
+    let mut map = generate(&config).expect("Failed to generate").map;
+    map.paths.clear();
+    map
+}
+
+/// The original recursive search, kept to compare against
+fn recursive_paths(map: &Map, end: u8) -> Vec<Vec<Position>> {
+    fn climb(map: &Map, visited: &mut VecDeque<Position>, end: u8, paths: &mut Vec<Vec<Position>>) {
+        let current = *visited.back().expect("Empty");
+        let val = map.map[current.y * map.width + current.x];
+
+        let mut neighbours = vec![];
+        if current.y > 0 {
+            neighbours.push(Position {
+                x: current.x,
+                y: current.y - 1,
+            });
+        }
+        if current.y < map.height - 1 {
+            neighbours.push(Position {
+                x: current.x,
+                y: current.y + 1,
+            });
+        }
+        if current.x < map.width - 1 {
+            neighbours.push(Position {
+                x: current.x + 1,
+                y: current.y,
+            });
+        }
+        if current.x > 0 {
+            neighbours.push(Position {
+                x: current.x - 1,
+                y: current.y,
+            });
+        }
+
+        for pos in neighbours {
+            let neighbour_val = map.map[pos.y * map.width + pos.x];
+            if visited.contains(&pos) || neighbour_val != val + 1 {
+                continue;
+            }
+            visited.push_back(pos);
+            match neighbour_val == end {
+                true => paths.push(visited.iter().copied().collect()),
+                false => climb(map, visited, end, paths),
+            }
+            visited.pop_back();
+        }
+    }
+
+    let mut paths = vec![];
+    for start in &map.trailheads {
+        let mut visited = VecDeque::from([*start]);
+        climb(map, &mut visited, end, &mut paths);
+    }
+    paths
+}
+
+fn find_paths(c: &mut Criterion) {
+    let mut group = c.benchmark_group("find_all_paths");
+    for size in [45, 100, 200] {
+        let mut map = dense_map(size);
+
+        group.bench_with_input(BenchmarkId::new("recursive", size), &size, |b, _| {
+            b.iter(|| recursive_paths(black_box(&map), TRAIL_LENGTH as u8 - 1))
+        });
+        group.bench_with_input(BenchmarkId::new("iterative", size), &size, |b, _| {
+            b.iter(|| {
+                map.paths.clear();
+                map.find_all_paths(black_box(TRAIL_LENGTH))
+                    .expect("Too many paths");
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("ratings", size), &size, |b, _| {
+            b.iter(|| map.ratings(black_box(TRAIL_LENGTH)))
+        });
+    }
+    group.finish();
+}
+
+fn generate_dense(c: &mut Criterion) {
+    let config = GenerationConfig::builder("benchmark")
+        .canvas_size(200)
+        .density(6)
+        .build()
+        .expect("Invalid config");
+    c.bench_function("generate 200x200", |b| {
+        b.iter(|| generate(black_box(&config)).expect("Failed to generate"))
+    });
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = find_paths, generate_dense
+}
+criterion_main!(benches);
@@ -0,0 +1,150 @@
+use std::{fmt::Display, str::FromStr};
+
+/// How heights are written in a text map
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Alphabet {
+    /// One character per cell, 0-9
+    #[default]
+    Digits,
+    /// One character per cell, 0-9 then a-z
+    Base36,
+    /// Whitespace separated integers, 0-255
+    Separated,
+}
+
+impl Alphabet {
+    /// Longest trail which can be written, ie the number of distinct heights
+    pub fn max_trail_length(&self) -> usize {
+        match self {
+            Alphabet::Digits => 10,
+            Alphabet::Base36 => 36,
+            Alphabet::Separated => u8::MAX as usize + 1,
+        }
+    }
+
+    /// Smallest alphabet which can hold every height in a trail
+    pub fn for_trail_length(trail_length: usize) -> Self {
+        [Alphabet::Digits, Alphabet::Base36, Alphabet::Separated]
+            .into_iter()
+            .find(|alphabet| trail_length <= alphabet.max_trail_length())
+            .unwrap_or(Alphabet::Separated)
+    }
+
+    /// Split a row into cells, with the character index each cell starts at
+    pub(crate) fn cells<'a>(&self, row: &'a str) -> Vec<(usize, &'a str)> {
+        match self {
+            Alphabet::Digits | Alphabet::Base36 => row
+                .char_indices()
+                .enumerate()
+                .map(|(column, (byte, c))| (column, &row[byte..byte + c.len_utf8()]))
+                .collect(),
+            Alphabet::Separated => {
+                let mut cells = vec![];
+                let mut start = None;
+                for (column, (byte, c)) in row.char_indices().enumerate() {
+                    match (c.is_whitespace(), start) {
+                        (false, None) => start = Some((column, byte)),
+                        (true, Some((start_column, start_byte))) => {
+                            cells.push((start_column, &row[start_byte..byte]));
+                            start = None;
+                        }
+                        _ => {}
+                    }
+                }
+                if let Some((start_column, start_byte)) = start {
+                    cells.push((start_column, &row[start_byte..]));
+                }
+                cells
+            }
+        }
+    }
+
+    pub fn decode(&self, cell: &str) -> Option<u8> {
+        match self {
+            Alphabet::Digits | Alphabet::Base36 => {
+                let mut chars = cell.chars();
+                let radix = self.max_trail_length() as u32;
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => c.to_digit(radix).map(|val| val as u8),
+                    _ => None,
+                }
+            }
+            Alphabet::Separated => cell.parse().ok(),
+        }
+    }
+
+    pub fn encode(&self, height: u8) -> String {
+        match self {
+            Alphabet::Digits | Alphabet::Base36 => {
+                char::from_digit(height as u32, self.max_trail_length() as u32)
+                    .map(String::from)
+                    .unwrap_or_else(|| String::from("?"))
+            }
+            Alphabet::Separated => height.to_string(),
+        }
+    }
+}
+
+impl Display for Alphabet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Alphabet::Digits => write!(f, "digits"),
+            Alphabet::Base36 => write!(f, "base36"),
+            Alphabet::Separated => write!(f, "separated"),
+        }
+    }
+}
+
+impl FromStr for Alphabet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "digits" => Ok(Alphabet::Digits),
+            "base36" => Ok(Alphabet::Base36),
+            "separated" => Ok(Alphabet::Separated),
+            _ => Err(format!(
+                "Unknown alphabet {s:?}, expected digits, base36 or separated"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::alphabet::Alphabet;
+
+    #[test]
+    fn it_should_pick_smallest_alphabet() {
+        assert_eq!(Alphabet::for_trail_length(4), Alphabet::Digits);
+        assert_eq!(Alphabet::for_trail_length(10), Alphabet::Digits);
+        assert_eq!(Alphabet::for_trail_length(11), Alphabet::Base36);
+        assert_eq!(Alphabet::for_trail_length(36), Alphabet::Base36);
+        assert_eq!(Alphabet::for_trail_length(37), Alphabet::Separated);
+    }
+
+    #[test]
+    fn it_should_encode_and_decode() {
+        assert_eq!(Alphabet::Digits.encode(7), "7");
+        assert_eq!(Alphabet::Digits.decode("7"), Some(7));
+        assert_eq!(Alphabet::Digits.decode("a"), None);
+        assert_eq!(Alphabet::Base36.encode(29), "t");
+        assert_eq!(Alphabet::Base36.decode("t"), Some(29));
+        assert_eq!(Alphabet::Base36.decode("T"), Some(29));
+        assert_eq!(Alphabet::Separated.encode(200), "200");
+        assert_eq!(Alphabet::Separated.decode("200"), Some(200));
+        assert_eq!(Alphabet::Separated.decode("256"), None);
+    }
+
+    #[test]
+    fn it_should_split_cells() {
+        assert_eq!(
+            Alphabet::Digits.cells("012"),
+            vec![(0, "0"), (1, "1"), (2, "2")]
+        );
+        assert_eq!(
+            Alphabet::Separated.cells("0  12 3"),
+            vec![(0, "0"), (3, "12"), (6, "3")]
+        );
+    }
+}
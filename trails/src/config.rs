@@ -0,0 +1,519 @@
+use std::fmt::Display;
+
+use crate::{
+    alphabet::Alphabet,
+    curve::PathStyle,
+    error::Error,
+    fill::FillStrategy,
+    input::GrowthRules,
+    mask::Shape,
+    order::PathOrdering,
+    palette::{ColourStrategy, Palette},
+    partition::Partition,
+    placement::Placement,
+    quadtree::SplitRules,
+    select::Selection,
+    topology::Neighbourhood,
+};
+
+/// Heights 0-9, as in the original puzzle
+pub const DEFAULT_TRAIL_LENGTH: usize = 10;
+
+/// Size of rounded or chamfered corners, for `Style` and `Svg::new`
+pub const DEFAULT_CORNER_RADIUS: usize = 8;
+
+/// Drawing options passed through to `Svg`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Style {
+    pub tile_size: usize,
+    pub offset: usize,
+    pub stroke_width: usize,
+    pub colour: String,
+    pub end_radius: usize,
+    /// Colour trails individually instead of using `colour` for everything
+    pub palette: Option<Palette>,
+    pub colour_strategy: ColourStrategy,
+    pub path_style: PathStyle,
+    /// Size of rounded or chamfered corners
+    pub corner_radius: usize,
+    /// Order trails are drawn in, which matters for plotters
+    pub ordering: PathOrdering,
+    /// Draw segments shared by several trails once, as the fewest lines
+    pub merge: bool,
+    /// Draw the grid, leaves and start points over the trails
+    pub debug_overlay: bool,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            tile_size: 32,
+            offset: 16,
+            stroke_width: 2,
+            colour: String::from("black"),
+            end_radius: 10,
+            palette: None,
+            colour_strategy: ColourStrategy::default(),
+            path_style: PathStyle::default(),
+            corner_radius: DEFAULT_CORNER_RADIUS,
+            ordering: PathOrdering::default(),
+            merge: false,
+            debug_overlay: false,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ConfigError {
+    MinLeafSize,
+    SplitProbability(f64),
+    TrailLength { len: usize, max: usize },
+    EndRadius { end_radius: usize, offset: usize },
+    MaxTrails,
+    TargetTrails,
+    WrapHex,
+    PlacementSize(f64),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::MinLeafSize => write!(f, "Min leaf size must be at least 1"),
+            ConfigError::SplitProbability(probability) => {
+                write!(f, "Split probability {probability} must be between 0 and 1")
+            }
+            ConfigError::TrailLength { len, max } => {
+                write!(f, "Trail length {len} must be between 2 and {max}")
+            }
+            ConfigError::EndRadius { end_radius, offset } => write!(
+                f,
+                "End radius {end_radius} is larger than the canvas offset {offset}"
+            ),
+            ConfigError::MaxTrails => write!(f, "Max trails must be at least 1"),
+            ConfigError::TargetTrails => write!(f, "Target trails must be at least 1"),
+            ConfigError::WrapHex => write!(f, "Hex maps can't wrap around"),
+            ConfigError::PlacementSize(size) => {
+                write!(
+                    f,
+                    "Placement size {size} must be a number of cells, at least 1"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Checks shared by anything that finds trails on a map, generated or read
+/// from a file
+pub fn validate_trails(
+    trail_length: usize,
+    alphabet: Alphabet,
+    max_trails: Option<usize>,
+    wrap: bool,
+    neighbourhood: Neighbourhood,
+) -> Result<(), Error> {
+    let max = alphabet.max_trail_length();
+    if !(2..=max).contains(&trail_length) {
+        return Err(ConfigError::TrailLength {
+            len: trail_length,
+            max,
+        }
+        .into());
+    }
+    if max_trails == Some(0) {
+        return Err(ConfigError::MaxTrails.into());
+    }
+    if wrap && neighbourhood == Neighbourhood::Hex {
+        return Err(ConfigError::WrapHex.into());
+    }
+    Ok(())
+}
+
+/// Everything needed to create an image from a seed
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenerationConfig {
+    pub seed: String,
+    pub width: usize,
+    pub height: usize,
+    /// How the canvas is split into leaves
+    pub partition: Partition,
+    /// When quadtree and BSP leaves split
+    pub split: SplitRules,
+    pub min_leaf_size: usize,
+    pub density: u8,
+    /// Where trails start within each leaf
+    pub placement: Placement,
+    /// Retries and target for starts which don't grow a trail
+    pub growth: GrowthRules,
+    pub trail_length: usize,
+    /// Defaults to the smallest alphabet which fits `trail_length`
+    pub alphabet: Option<Alphabet>,
+    /// Whether trails can step diagonally
+    pub neighbourhood: Neighbourhood,
+    /// Trails cross the edges so the image tiles seamlessly
+    pub wrap: bool,
+    /// Area of the canvas trails stay inside
+    pub mask: Option<Shape>,
+    /// How cells between trails are filled
+    pub fill: FillStrategy,
+    /// Which of the trails found are drawn
+    pub selection: Selection,
+    /// Cap on trails drawn, picked at random from the seed
+    pub max_trails: Option<usize>,
+    pub style: Style,
+}
+
+impl GenerationConfig {
+    pub fn builder(seed: &str) -> GenerationConfigBuilder {
+        GenerationConfigBuilder {
+            config: GenerationConfig {
+                seed: seed.to_string(),
+                width: 45,
+                height: 45,
+                partition: Partition::default(),
+                split: SplitRules::default(),
+                min_leaf_size: 3,
+                density: 2,
+                placement: Placement::default(),
+                growth: GrowthRules::default(),
+                trail_length: DEFAULT_TRAIL_LENGTH,
+                alphabet: None,
+                neighbourhood: Neighbourhood::default(),
+                wrap: false,
+                mask: None,
+                fill: FillStrategy::default(),
+                selection: Selection::default(),
+                max_trails: None,
+                style: Style::default(),
+            },
+        }
+    }
+
+    pub fn alphabet(&self) -> Alphabet {
+        self.alphabet
+            .unwrap_or(Alphabet::for_trail_length(self.trail_length))
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        let cells = self.width.checked_mul(self.height).ok_or(Error::Overflow)?;
+        if self.width == 0 || self.height == 0 || cells < self.trail_length {
+            return Err(Error::CanvasTooSmall {
+                width: self.width,
+                height: self.height,
+            });
+        }
+        if self.min_leaf_size == 0 {
+            return Err(ConfigError::MinLeafSize.into());
+        }
+        if !(0.0..=1.0).contains(&self.split.probability) {
+            return Err(ConfigError::SplitProbability(self.split.probability).into());
+        }
+        validate_trails(
+            self.trail_length,
+            self.alphabet(),
+            self.max_trails,
+            self.wrap,
+            self.neighbourhood,
+        )?;
+        if self.growth.target == Some(0) {
+            return Err(ConfigError::TargetTrails.into());
+        }
+        if let Placement::PoissonDisk { min_distance: size } | Placement::Noise { scale: size } =
+            self.placement
+            && !(size.is_finite() && size >= 1.0)
+        {
+            return Err(ConfigError::PlacementSize(size).into());
+        }
+        // start and end markers are drawn around the outermost points,
+        // which are half a tile from the edge of a wrapped map
+        let offset = match self.wrap {
+            true => self.style.tile_size / 2,
+            false => self.style.offset,
+        };
+        if self.style.end_radius > offset {
+            return Err(ConfigError::EndRadius {
+                end_radius: self.style.end_radius,
+                offset,
+            }
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GenerationConfigBuilder {
+    config: GenerationConfig,
+}
+
+impl GenerationConfigBuilder {
+    pub fn width(mut self, width: usize) -> Self {
+        self.config.width = width;
+        self
+    }
+
+    pub fn height(mut self, height: usize) -> Self {
+        self.config.height = height;
+        self
+    }
+
+    /// Set width and height to the same value
+    pub fn canvas_size(self, size: usize) -> Self {
+        self.width(size).height(size)
+    }
+
+    pub fn partition(mut self, partition: Partition) -> Self {
+        self.config.partition = partition;
+        self
+    }
+
+    /// Chance of leaves splitting once they're past the forced split depth
+    pub fn split_probability(mut self, probability: f64) -> Self {
+        self.config.split.probability = probability;
+        self
+    }
+
+    /// Leaves this deep or shallower always split
+    pub fn forced_split_depth(mut self, depth: usize) -> Self {
+        self.config.split.forced_depth = depth;
+        self
+    }
+
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.config.split.max_depth = Some(depth);
+        self
+    }
+
+    pub fn min_leaf_size(mut self, min_leaf_size: usize) -> Self {
+        self.config.min_leaf_size = min_leaf_size;
+        self
+    }
+
+    pub fn density(mut self, density: u8) -> Self {
+        self.config.density = density;
+        self
+    }
+
+    pub fn placement(mut self, placement: Placement) -> Self {
+        self.config.placement = placement;
+        self
+    }
+
+    /// Other cells of the same leaf to try when a start doesn't grow a trail
+    pub fn retries(mut self, retries: usize) -> Self {
+        self.config.growth.retries = retries;
+        self
+    }
+
+    /// Keep adding starts until this many trails are planted
+    pub fn target_trails(mut self, target: usize) -> Self {
+        self.config.growth.target = Some(target);
+        self
+    }
+
+    /// Most starts to try, including retries
+    pub fn attempt_budget(mut self, budget: usize) -> Self {
+        self.config.growth.budget = Some(budget);
+        self
+    }
+
+    pub fn trail_length(mut self, trail_length: usize) -> Self {
+        self.config.trail_length = trail_length;
+        self
+    }
+
+    pub fn alphabet(mut self, alphabet: Alphabet) -> Self {
+        self.config.alphabet = Some(alphabet);
+        self
+    }
+
+    pub fn neighbourhood(mut self, neighbourhood: Neighbourhood) -> Self {
+        self.config.neighbourhood = neighbourhood;
+        self
+    }
+
+    pub fn wrap(mut self, wrap: bool) -> Self {
+        self.config.wrap = wrap;
+        self
+    }
+
+    pub fn mask(mut self, mask: Shape) -> Self {
+        self.config.mask = Some(mask);
+        self
+    }
+
+    pub fn fill(mut self, fill: FillStrategy) -> Self {
+        self.config.fill = fill;
+        self
+    }
+
+    pub fn selection(mut self, selection: Selection) -> Self {
+        self.config.selection = selection;
+        self
+    }
+
+    pub fn max_trails(mut self, max_trails: usize) -> Self {
+        self.config.max_trails = Some(max_trails);
+        self
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.config.style = style;
+        self
+    }
+
+    pub fn build(self) -> Result<GenerationConfig, Error> {
+        self.config.validate()?;
+        Ok(self.config)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        alphabet::Alphabet,
+        config::{ConfigError, GenerationConfig, Style},
+        error::Error,
+        fill::FillStrategy,
+        input::GrowthRules,
+        partition::Partition,
+        placement::Placement,
+        quadtree::SplitRules,
+        select::Selection,
+        topology::Neighbourhood,
+    };
+
+    #[test]
+    fn it_should_build_default_config() {
+        let config = GenerationConfig::builder("abc").build();
+        assert_eq!(
+            config,
+            Ok(GenerationConfig {
+                seed: String::from("abc"),
+                width: 45,
+                height: 45,
+                partition: Partition::Quadtree,
+                split: SplitRules::default(),
+                min_leaf_size: 3,
+                density: 2,
+                placement: Placement::PerLeaf,
+                growth: GrowthRules::default(),
+                trail_length: 10,
+                alphabet: None,
+                neighbourhood: Neighbourhood::VonNeumann,
+                wrap: false,
+                mask: None,
+                fill: FillStrategy::Random,
+                selection: Selection::All,
+                max_trails: None,
+                style: Style::default(),
+            })
+        );
+    }
+
+    #[test]
+    fn it_should_reject_invalid_config() {
+        assert_eq!(
+            GenerationConfig::builder("abc").width(0).build(),
+            Err(Error::CanvasTooSmall {
+                width: 0,
+                height: 45
+            })
+        );
+        assert_eq!(
+            GenerationConfig::builder("abc").canvas_size(3).build(),
+            Err(Error::CanvasTooSmall {
+                width: 3,
+                height: 3
+            })
+        );
+        assert_eq!(
+            GenerationConfig::builder("abc").min_leaf_size(0).build(),
+            Err(Error::Config(ConfigError::MinLeafSize))
+        );
+        assert_eq!(
+            GenerationConfig::builder("abc")
+                .split_probability(1.5)
+                .build(),
+            Err(Error::Config(ConfigError::SplitProbability(1.5)))
+        );
+        assert_eq!(
+            GenerationConfig::builder("abc").max_trails(0).build(),
+            Err(Error::Config(ConfigError::MaxTrails))
+        );
+        assert_eq!(
+            GenerationConfig::builder("abc").target_trails(0).build(),
+            Err(Error::Config(ConfigError::TargetTrails))
+        );
+        assert_eq!(
+            GenerationConfig::builder("abc").trail_length(1).build(),
+            Err(Error::Config(ConfigError::TrailLength { len: 1, max: 10 }))
+        );
+        assert_eq!(
+            GenerationConfig::builder("abc")
+                .trail_length(11)
+                .alphabet(Alphabet::Digits)
+                .build(),
+            Err(Error::Config(ConfigError::TrailLength { len: 11, max: 10 }))
+        );
+        assert_eq!(
+            GenerationConfig::builder("abc")
+                .trail_length(300)
+                .build()
+                .map(|config| config.trail_length),
+            Err(Error::Config(ConfigError::TrailLength {
+                len: 300,
+                max: 256
+            }))
+        );
+        assert_eq!(
+            GenerationConfig::builder("abc")
+                .style(Style {
+                    end_radius: 20,
+                    ..Style::default()
+                })
+                .build(),
+            Err(Error::Config(ConfigError::EndRadius {
+                end_radius: 20,
+                offset: 16
+            }))
+        );
+        assert_eq!(
+            GenerationConfig::builder("abc")
+                .neighbourhood(Neighbourhood::Hex)
+                .wrap(true)
+                .build(),
+            Err(Error::Config(ConfigError::WrapHex))
+        );
+        assert_eq!(
+            GenerationConfig::builder("abc")
+                .placement(Placement::PoissonDisk {
+                    min_distance: f64::INFINITY
+                })
+                .build(),
+            Err(Error::Config(ConfigError::PlacementSize(f64::INFINITY)))
+        );
+        assert_eq!(
+            GenerationConfig::builder("abc")
+                .wrap(true)
+                .style(Style {
+                    tile_size: 16,
+                    ..Style::default()
+                })
+                .build(),
+            Err(Error::Config(ConfigError::EndRadius {
+                end_radius: 10,
+                offset: 8
+            }))
+        );
+        assert_eq!(
+            GenerationConfig::builder("abc")
+                .width(usize::MAX)
+                .height(2)
+                .build(),
+            Err(Error::Overflow)
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use std::{fmt::Display, str::FromStr};
+
+/// How a trail's corners are drawn
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum PathStyle {
+    /// Straight lines with right angled corners
+    #[default]
+    Square,
+    /// Corners replaced by a circular arc
+    Rounded,
+    /// Catmull-Rom spline through every point of the trail
+    Smooth,
+    /// Corners cut off with a diagonal line
+    Chamfer,
+}
+
+impl Display for PathStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PathStyle::Square => write!(f, "square"),
+            PathStyle::Rounded => write!(f, "rounded"),
+            PathStyle::Smooth => write!(f, "smooth"),
+            PathStyle::Chamfer => write!(f, "chamfer"),
+        }
+    }
+}
+
+impl FromStr for PathStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "square" => Ok(PathStyle::Square),
+            "rounded" => Ok(PathStyle::Rounded),
+            "smooth" => Ok(PathStyle::Smooth),
+            "chamfer" => Ok(PathStyle::Chamfer),
+            _ => Err(format!(
+                "Unknown path style {s:?}, expected square, rounded, smooth or chamfer"
+            )),
+        }
+    }
+}
+
+pub type Point = (f64, f64);
+
+/// Format a coordinate with at most two decimal places
+fn num(val: f64) -> String {
+    let rounded = (val * 100.0).round() / 100.0;
+    // avoid "-0"
+    if rounded == 0.0 {
+        return String::from("0");
+    }
+    format!("{rounded}")
+}
+
+fn point(p: Point) -> String {
+    format!("{},{}", num(p.0), num(p.1))
+}
+
+fn distance(a: Point, b: Point) -> f64 {
+    ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt()
+}
+
+/// Point `dist` along the line from `from` towards `to`
+fn towards(from: Point, to: Point, dist: f64) -> Point {
+    let len = distance(from, to);
+    if len == 0.0 {
+        return from;
+    }
+    (
+        from.0 + (to.0 - from.0) * dist / len,
+        from.1 + (to.1 - from.1) * dist / len,
+    )
+}
+
+/// Polyline through corner points, with each corner cut back by up to
+/// `radius` and joined with an arc, or a straight line if `arc` is false.
+/// Cuts are limited to half of each segment so neighbouring corners
+/// don't overlap.
+pub fn cut_corners(points: &[Point], radius: f64, arc: bool) -> String {
+    let Some(first) = points.first() else {
+        return String::new();
+    };
+    let mut output = format!("M{}", point(*first));
+
+    for window in points.windows(3) {
+        let (prev, corner, next) = (window[0], window[1], window[2]);
+        let cut = radius
+            .min(distance(prev, corner) / 2.0)
+            .min(distance(corner, next) / 2.0);
+        let entry = towards(corner, prev, cut);
+        let exit = towards(corner, next, cut);
+
+        output += &format!("L{}", point(entry));
+        // turning clockwise on screen (y points down) is a positive sweep
+        let cross =
+            (corner.0 - prev.0) * (next.1 - corner.1) - (corner.1 - prev.1) * (next.0 - corner.0);
+        if arc && cross != 0.0 {
+            // the arc touching both segments `cut` from the corner has a
+            // radius of cut * tan(half the angle between them)
+            let dot = (prev.0 - corner.0) * (next.0 - corner.0)
+                + (prev.1 - corner.1) * (next.1 - corner.1);
+            let interior = cross.abs().atan2(dot);
+            let r = cut * (interior / 2.0).tan();
+            let sweep = if cross > 0.0 { 1 } else { 0 };
+            output += &format!("A{},{} 0 0 {} {}", num(r), num(r), sweep, point(exit));
+        } else {
+            output += &format!("L{}", point(exit));
+        }
+    }
+
+    if points.len() > 1 {
+        output += &format!("L{}", point(points[points.len() - 1]));
+    }
+    output
+}
+
+/// Catmull-Rom spline through every point, written as cubic beziers.
+/// The first and last points are repeated so the curve reaches the ends.
+pub fn catmull_rom(points: &[Point]) -> String {
+    let Some(first) = points.first() else {
+        return String::new();
+    };
+    let mut output = format!("M{}", point(*first));
+
+    for i in 0..points.len().saturating_sub(1) {
+        let p0 = points[i.saturating_sub(1)];
+        let p1 = points[i];
+        let p2 = points[i + 1];
+        let p3 = points[(i + 2).min(points.len() - 1)];
+
+        let c1 = (p1.0 + (p2.0 - p0.0) / 6.0, p1.1 + (p2.1 - p0.1) / 6.0);
+        let c2 = (p2.0 - (p3.0 - p1.0) / 6.0, p2.1 - (p3.1 - p1.1) / 6.0);
+        output += &format!("C{} {} {}", point(c1), point(c2), point(p2));
+    }
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use crate::curve::{PathStyle, catmull_rom, cut_corners};
+
+    #[test]
+    fn it_should_round_corners() {
+        let points = [(0.0, 0.0), (64.0, 0.0), (64.0, 64.0), (0.0, 64.0)];
+        assert_eq!(
+            cut_corners(&points, 8.0, true),
+            "M0,0L56,0A8,8 0 0 1 64,8L64,56A8,8 0 0 1 56,64L0,64"
+        );
+    }
+
+    #[test]
+    fn it_should_round_diagonal_corners() {
+        // 135 degrees between the segments, so the radius is 8 * tan(67.5)
+        let points = [(0.0, 0.0), (64.0, 0.0), (128.0, 64.0)];
+        assert_eq!(
+            cut_corners(&points, 8.0, true),
+            "M0,0L56,0A19.31,19.31 0 0 1 69.66,5.66L128,64"
+        );
+    }
+
+    #[test]
+    fn it_should_limit_radius_to_half_segment() {
+        let points = [(0.0, 0.0), (10.0, 0.0), (10.0, -64.0)];
+        assert_eq!(
+            cut_corners(&points, 8.0, true),
+            "M0,0L5,0A5,5 0 0 0 10,-5L10,-64"
+        );
+    }
+
+    #[test]
+    fn it_should_chamfer_corners() {
+        let points = [(0.0, 0.0), (64.0, 0.0), (64.0, 64.0)];
+        assert_eq!(cut_corners(&points, 8.0, false), "M0,0L56,0L64,8L64,64");
+    }
+
+    #[test]
+    fn it_should_smooth_points() {
+        let points = [(0.0, 0.0), (60.0, 0.0), (60.0, 60.0)];
+        assert_eq!(
+            catmull_rom(&points),
+            "M0,0C10,0 50,-10 60,0C70,10 60,50 60,60"
+        );
+    }
+
+    #[test]
+    fn it_should_parse_path_style() {
+        assert_eq!("rounded".parse::<PathStyle>(), Ok(PathStyle::Rounded));
+        assert!("wiggly".parse::<PathStyle>().is_err());
+    }
+}
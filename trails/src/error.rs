@@ -0,0 +1,107 @@
+use std::fmt::Display;
+
+use crate::config::ConfigError;
+
+/// Problem found while parsing a map. Line and column start at 1
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub column: usize,
+    pub kind: DiagnosticKind,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DiagnosticKind {
+    /// Character which isn't a height
+    InvalidChar(char),
+    /// Multi-character cell which isn't a height
+    InvalidValue(String),
+    /// Row which is a different length to the first row
+    RaggedRow {
+        expected: usize,
+        found: usize,
+    },
+    BlankLine,
+    /// Windows line ending
+    CarriageReturn,
+    TrailingWhitespace,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}: ", self.line, self.column)?;
+        match &self.kind {
+            DiagnosticKind::InvalidChar(found) => write!(f, "invalid character {found:?}"),
+            DiagnosticKind::InvalidValue(found) => write!(f, "invalid height {found:?}"),
+            DiagnosticKind::RaggedRow { expected, found } => {
+                write!(f, "row has {found} columns but expected {expected}")
+            }
+            DiagnosticKind::BlankLine => write!(f, "blank line"),
+            DiagnosticKind::CarriageReturn => write!(f, "carriage return (CRLF line ending)"),
+            DiagnosticKind::TrailingWhitespace => write!(f, "trailing whitespace"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// Every problem found in a map, in the order they appear
+    Parse(Vec<Diagnostic>),
+    EmptyInput,
+    CanvasTooSmall {
+        width: usize,
+        height: usize,
+    },
+    /// Trail with fewer than two points, so there's nothing to draw
+    TrailTooShort {
+        len: usize,
+    },
+    /// Size or coordinate too large to represent
+    Overflow,
+    /// More routes up a map than can be held in memory
+    TooManyPaths {
+        max: usize,
+    },
+    /// Failed to rasterise a drawing
+    Render(String),
+    /// Image which couldn't be read
+    Image(String),
+    Config(ConfigError),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Parse(diagnostics) => {
+                write!(f, "Failed to parse map")?;
+                for diagnostic in diagnostics {
+                    write!(f, "\n  {diagnostic}")?;
+                }
+                Ok(())
+            }
+            Error::EmptyInput => write!(f, "Input is empty"),
+            Error::CanvasTooSmall { width, height } => {
+                write!(f, "Canvas {width}x{height} is too small to hold a trail")
+            }
+            Error::TrailTooShort { len } => {
+                write!(f, "Trail has {len} points but needs at least 2")
+            }
+            Error::Overflow => write!(f, "Size is too large"),
+            Error::TooManyPaths { max } => write!(
+                f,
+                "Map has more than {max} trails, set max trails to draw a sample of them"
+            ),
+            Error::Render(err) => write!(f, "Failed to render: {err}"),
+            Error::Image(err) => write!(f, "Failed to read image: {err}"),
+            Error::Config(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ConfigError> for Error {
+    fn from(value: ConfigError) -> Self {
+        Self::Config(value)
+    }
+}
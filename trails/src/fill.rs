@@ -0,0 +1,143 @@
+use std::{fmt::Display, str::FromStr};
+
+use rand::{rngs::SmallRng, seq::IndexedRandom};
+
+/// How cells which aren't on a trail get their heights
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum FillStrategy {
+    /// Any height, which can join up into extra trails by chance
+    #[default]
+    Random,
+    /// Random heights which never complete a trail, so the trails found are
+    /// the ones planted. Cells where every height would are left blank.
+    Safe,
+    /// As `Safe`, but each cell extends the climbs next to it as far as it
+    /// can, leaving chains which stop one step short of a trail
+    NearMiss,
+}
+
+impl Display for FillStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FillStrategy::Random => write!(f, "random"),
+            FillStrategy::Safe => write!(f, "safe"),
+            FillStrategy::NearMiss => write!(f, "near-miss"),
+        }
+    }
+}
+
+impl FromStr for FillStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(FillStrategy::Random),
+            "safe" => Ok(FillStrategy::Safe),
+            "near-miss" => Ok(FillStrategy::NearMiss),
+            _ => Err(format!(
+                "Unknown fill {s:?}, expected random, safe or near-miss"
+            )),
+        }
+    }
+}
+
+/// Longest climb one step at a time through a neighbour at `height`, plus
+/// the cell itself
+fn longest(map: &[Option<u8>], cells: &[usize], lengths: &[usize], height: Option<u8>) -> usize {
+    1 + cells
+        .iter()
+        .filter(|cell| height.is_some() && map[**cell] == height)
+        .map(|cell| lengths[*cell])
+        .max()
+        .unwrap_or(0)
+}
+
+/// Fill the empty `open` cells of `map` in order. Every cell tracks the
+/// longest climb ending at it and starting from it, and a height is only
+/// used if the climb through it stays shorter than `trail_length`, so no
+/// new trail is ever completed.
+pub(crate) fn fill_safe(
+    map: &mut [Option<u8>],
+    adjacent: &[Vec<usize>],
+    open: &[bool],
+    trail_length: usize,
+    near_miss: bool,
+    rng: &mut SmallRng,
+) {
+    let mut below = vec![0; map.len()];
+    let mut above = vec![0; map.len()];
+    let mut filled: Vec<usize> = (0..map.len())
+        .filter(|index| map[*index].is_some())
+        .collect();
+    filled.sort_by_key(|index| map[*index]);
+    for index in &filled {
+        let height = map[*index].and_then(|height| height.checked_sub(1));
+        below[*index] = longest(map, &adjacent[*index], &below, height);
+    }
+    for index in filled.iter().rev() {
+        let height = map[*index].and_then(|height| height.checked_add(1));
+        above[*index] = longest(map, &adjacent[*index], &above, height);
+    }
+
+    for index in 0..map.len() {
+        if map[index].is_some() || !open[index] {
+            continue;
+        }
+
+        // height, and the longest climb through it
+        let options: Vec<(u8, usize, usize)> = (0..trail_length)
+            .map(|height| height as u8)
+            .map(|height| {
+                let down = longest(map, &adjacent[index], &below, height.checked_sub(1));
+                let up = longest(map, &adjacent[index], &above, height.checked_add(1));
+                (height, down, up)
+            })
+            .filter(|(_, down, up)| down + up - 1 < trail_length)
+            .collect();
+        let options = match near_miss {
+            true => {
+                let best = options.iter().map(|(_, down, up)| down + up).max();
+                options
+                    .into_iter()
+                    .filter(|(_, down, up)| Some(down + up) == best)
+                    .collect()
+            }
+            false => options,
+        };
+        let Some((height, down, up)) = options.choose(rng).copied() else {
+            continue;
+        };
+        map[index] = Some(height);
+        below[index] = down;
+        above[index] = up;
+
+        // longer climbs through this cell reach further up and down
+        let mut stack = vec![index];
+        while let Some(cell) = stack.pop() {
+            for next in &adjacent[cell] {
+                let step =
+                    map[*next].and_then(|next| map[cell].map(|cell| next as i16 - cell as i16));
+                if step == Some(1) && below[*next] < below[cell] + 1 {
+                    below[*next] = below[cell] + 1;
+                    stack.push(*next);
+                }
+                if step == Some(-1) && above[*next] < above[cell] + 1 {
+                    above[*next] = above[cell] + 1;
+                    stack.push(*next);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::fill::FillStrategy;
+
+    #[test]
+    fn it_should_parse_fill_strategies() {
+        assert_eq!("near-miss".parse(), Ok(FillStrategy::NearMiss));
+        assert_eq!(FillStrategy::Safe.to_string(), "safe");
+        assert!("uniform".parse::<FillStrategy>().is_err());
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::map::{Map, Position};
+
+/// Grid point, with points under a marker split into one copy per edge so
+/// strokes end at markers instead of passing through them
+type Node = (Position, usize);
+
+/// Union of every trail, with each unit edge stored once
+#[derive(Debug, PartialEq, Clone)]
+pub struct TrailGraph {
+    /// Edges with the lower point first, and the first path which uses each
+    pub edges: BTreeMap<(Position, Position), usize>,
+    pub trailheads: BTreeSet<Position>,
+    pub summits: BTreeSet<Position>,
+}
+
+impl TrailGraph {
+    pub fn new(paths: &[Vec<Position>]) -> Self {
+        let mut edges = BTreeMap::new();
+        for (index, path) in paths.iter().enumerate() {
+            for pair in path.windows(2) {
+                let edge = (pair[0].min(pair[1]), pair[0].max(pair[1]));
+                edges.entry(edge).or_insert(index);
+            }
+        }
+
+        TrailGraph {
+            edges,
+            trailheads: paths
+                .iter()
+                .filter_map(|path| path.first())
+                .copied()
+                .collect(),
+            summits: paths
+                .iter()
+                .filter_map(|path| path.last())
+                .copied()
+                .collect(),
+        }
+    }
+
+    pub fn from_map(map: &Map) -> Self {
+        TrailGraph::new(&map.paths)
+    }
+
+    pub fn is_marker(&self, pos: &Position) -> bool {
+        self.trailheads.contains(pos) || self.summits.contains(pos)
+    }
+
+    /// First path which uses the edge between `a` and `b`
+    pub fn path_index(&self, a: Position, b: Position) -> Option<usize> {
+        self.edges.get(&(a.min(b), a.max(b))).copied()
+    }
+
+    /// Fewest lines which draw every edge exactly once without passing
+    /// through a marker. Each connected part needs one line per pair of odd
+    /// ends, so those are joined with made up edges, walked as a single
+    /// Euler circuit, and the circuit cut wherever it used a made up edge.
+    pub fn strokes(&self) -> Vec<Vec<Position>> {
+        // (from, to, real edge)
+        let mut links: Vec<(Node, Node, bool)> = vec![];
+        let mut adjacent: BTreeMap<Node, Vec<usize>> = BTreeMap::new();
+        let node = |pos: Position, id: usize| match self.is_marker(&pos) {
+            true => (pos, id + 1),
+            false => (pos, 0),
+        };
+
+        for (id, (a, b)) in self.edges.keys().enumerate() {
+            let (from, to) = (node(*a, id), node(*b, id));
+            adjacent.entry(from).or_default().push(links.len());
+            adjacent.entry(to).or_default().push(links.len());
+            links.push((from, to, true));
+        }
+
+        let mut seen: BTreeSet<Node> = BTreeSet::new();
+        let mut strokes = vec![];
+        let starts: Vec<Node> = adjacent.keys().copied().collect();
+
+        for start in starts {
+            if seen.contains(&start) {
+                continue;
+            }
+
+            // collect the connected part and pair up its odd ends
+            let mut part = vec![start];
+            seen.insert(start);
+            let mut index = 0;
+            while index < part.len() {
+                for link in &adjacent[&part[index]] {
+                    let (from, to, _) = links[*link];
+                    let other = if from == part[index] { to } else { from };
+                    if seen.insert(other) {
+                        part.push(other);
+                    }
+                }
+                index += 1;
+            }
+            part.sort();
+            let odd: Vec<Node> = part
+                .iter()
+                .filter(|node| adjacent[node].len() % 2 == 1)
+                .copied()
+                .collect();
+            for pair in odd.chunks(2) {
+                adjacent.entry(pair[0]).or_default().push(links.len());
+                adjacent.entry(pair[1]).or_default().push(links.len());
+                links.push((pair[0], pair[1], false));
+            }
+
+            let first = odd.first().copied().unwrap_or(start);
+            strokes.extend(self.split(&euler_circuit(first, &links, &adjacent)));
+        }
+
+        strokes
+    }
+
+    /// Cut a circuit of `(node, link used to reach it)` at made up edges
+    fn split(&self, circuit: &[(Node, Option<(usize, bool)>)]) -> Vec<Vec<Position>> {
+        let made_up = circuit
+            .iter()
+            .position(|(_, link)| matches!(link, Some((_, false))));
+        let Some(made_up) = made_up else {
+            return vec![circuit.iter().map(|(node, _)| node.0).collect()];
+        };
+
+        // start just after a made up edge and go round the loop once
+        let mut strokes = vec![];
+        let mut stroke = vec![circuit[made_up].0.0];
+        for step in 1..circuit.len() {
+            let (node, link) = circuit[(made_up + step - 1) % (circuit.len() - 1) + 1];
+            match link {
+                Some((_, false)) => {
+                    strokes.push(stroke);
+                    stroke = vec![node.0];
+                }
+                _ => stroke.push(node.0),
+            }
+        }
+        strokes
+    }
+}
+
+/// Hierholzer's algorithm, returning each node with the link used to get
+/// there. The first and last nodes are the same.
+fn euler_circuit(
+    start: Node,
+    links: &[(Node, Node, bool)],
+    adjacent: &BTreeMap<Node, Vec<usize>>,
+) -> Vec<(Node, Option<(usize, bool)>)> {
+    let mut used = vec![false; links.len()];
+    let mut next: BTreeMap<Node, usize> = BTreeMap::new();
+    let mut stack: Vec<(Node, Option<(usize, bool)>)> = vec![(start, None)];
+    let mut circuit = vec![];
+
+    while let Some((current, _)) = stack.last().copied() {
+        let options = &adjacent[&current];
+        let cursor = next.entry(current).or_default();
+        while *cursor < options.len() && used[options[*cursor]] {
+            *cursor += 1;
+        }
+
+        match options.get(*cursor) {
+            Some(link) => {
+                used[*link] = true;
+                let (from, to, real) = links[*link];
+                let other = if from == current { to } else { from };
+                stack.push((other, Some((*link, real))));
+            }
+            None => circuit.push(stack.pop().expect("Stack is empty")),
+        }
+    }
+
+    // nodes come off the stack in reverse walking order
+    circuit.reverse();
+    circuit
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeSet;
+
+    use crate::{
+        graph::TrailGraph,
+        map::{Map, Position},
+    };
+
+    fn unit_edges(strokes: &[Vec<Position>]) -> Vec<(Position, Position)> {
+        let mut edges: Vec<_> = strokes
+            .iter()
+            .flat_map(|stroke| stroke.windows(2).map(|w| (w[0].min(w[1]), w[0].max(w[1]))))
+            .collect();
+        edges.sort();
+        edges
+    }
+
+    #[test]
+    fn it_should_store_shared_edges_once() {
+        let input = "89010123
+78121874
+87430965
+96549874
+45678903
+32019012
+01329801
+10456732";
+        let mut map = Map::parse(input).expect("Failed to parse");
+        map.find_all_paths(10).expect("Too many paths");
+
+        let drawn: usize = map.paths.iter().map(|path| path.len() - 1).sum();
+        let graph = TrailGraph::from_map(&map);
+        assert!(graph.edges.len() < drawn);
+
+        let strokes = graph.strokes();
+        let edges = unit_edges(&strokes);
+        let unique: BTreeSet<_> = edges.iter().copied().collect();
+        assert_eq!(edges.len(), unique.len());
+        assert_eq!(unique, graph.edges.keys().copied().collect());
+    }
+
+    #[test]
+    fn it_should_draw_branches_with_fewest_strokes() {
+        // two trails sharing their first three steps, then splitting
+        let p = |x, y| Position { x, y };
+        let paths = vec![
+            vec![p(0, 0), p(1, 0), p(2, 0), p(3, 0), p(3, 1)],
+            vec![p(0, 0), p(1, 0), p(2, 0), p(3, 0), p(4, 0)],
+        ];
+        let graph = TrailGraph::new(&paths);
+        let strokes = graph.strokes();
+
+        assert_eq!(graph.edges.len(), 5);
+        // the three ends and the fork at (3, 0) are odd, so they pair up
+        // into two strokes, one of them through the fork
+        assert_eq!(strokes.len(), 2);
+        assert_eq!(unit_edges(&strokes).len(), 5);
+    }
+
+    #[test]
+    fn it_should_stop_strokes_at_markers() {
+        let p = |x, y| Position { x, y };
+        // two trails from the same trailhead in opposite directions
+        let paths = vec![vec![p(1, 0), p(0, 0)], vec![p(1, 0), p(2, 0)]];
+        let graph = TrailGraph::new(&paths);
+        let strokes = graph.strokes();
+
+        assert_eq!(strokes.len(), 2);
+        for stroke in strokes {
+            assert!(!stroke[1..stroke.len() - 1].contains(&p(1, 0)));
+        }
+    }
+}
@@ -1,145 +1,404 @@
 use rand::prelude::*;
 use std::fmt::Display;
 
-use crate::map::Position;
+use crate::{
+    alphabet::Alphabet,
+    config::DEFAULT_TRAIL_LENGTH,
+    fill::{FillStrategy, fill_safe},
+    map::Position,
+    mask::Mask,
+    quadtree::Leaf,
+    topology::Neighbourhood,
+};
+
+/// One bit per cell
+struct Visited {
+    bits: Vec<u64>,
+}
+
+impl Visited {
+    fn new(len: usize) -> Self {
+        Visited {
+            bits: vec![0; len.div_ceil(64)],
+        }
+    }
+
+    fn insert(&mut self, index: usize) {
+        self.bits[index / 64] |= 1 << (index % 64);
+    }
+
+    fn contains(&self, index: usize) -> bool {
+        self.bits[index / 64] & (1 << (index % 64)) != 0
+    }
+}
+
+/// How hard `Input::grow` tries to plant trails
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct GrowthRules {
+    /// Other cells of the same leaf to try when a start can't grow a trail
+    pub retries: usize,
+    /// Keep trying starts in random leaves until this many trails are planted
+    pub target: Option<usize>,
+    /// Most starts tried in all, including retries. Defaults to ten for each
+    /// target trail, or no limit without a target
+    pub budget: Option<usize>,
+}
+
+impl GrowthRules {
+    fn budget(&self) -> usize {
+        self.budget
+            .or(self.target.map(|target| target.saturating_mul(10)))
+            .unwrap_or(usize::MAX)
+    }
+}
+
+/// Starts tried by `Input::grow`
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct GrowthReport {
+    /// Starting points asked for, before retries
+    pub requested: usize,
+    /// Every start tried in order, and whether it grew a full trail
+    pub starts: Vec<(Position, bool)>,
+}
+
+impl GrowthReport {
+    /// Trails planted
+    pub fn planted(&self) -> usize {
+        self.starts.iter().filter(|(_, grown)| *grown).count()
+    }
+}
 
 #[derive(Debug, PartialEq)]
 pub struct Input {
-    pub map: Vec<char>,
+    /// Height of each cell, `None` until a trail or fill reaches it
+    pub map: Vec<Option<u8>>,
     pub width: usize,
     pub height: usize,
+    pub trail_length: usize,
+    pub alphabet: Alphabet,
+    /// Cells a trail can step to
+    pub neighbourhood: Neighbourhood,
+    /// Trails can step off one edge and back on at the opposite one
+    pub wrap: bool,
+    /// Cells trails and fill stay out of, left as `.`
+    pub mask: Option<Mask>,
+    /// How `fill` picks heights for cells not on a trail
+    pub fill_strategy: FillStrategy,
 }
 
 impl Input {
     pub fn new(width: usize, height: usize) -> Self {
         let len = width * height;
         Input {
-            map: vec!['.'; len],
+            map: vec![None; len],
             width,
             height,
+            trail_length: DEFAULT_TRAIL_LENGTH,
+            alphabet: Alphabet::Digits,
+            neighbourhood: Neighbourhood::default(),
+            wrap: false,
+            mask: None,
+            fill_strategy: FillStrategy::default(),
         }
     }
 
+    /// Grow trails of `trail_length` cells, written with the smallest
+    /// alphabet which can hold every height
+    pub fn with_trail_length(mut self, trail_length: usize) -> Self {
+        self.trail_length = trail_length;
+        self.alphabet = Alphabet::for_trail_length(trail_length);
+        self
+    }
+
+    pub fn with_alphabet(mut self, alphabet: Alphabet) -> Self {
+        self.alphabet = alphabet;
+        self
+    }
+
+    /// Let trails step diagonally
+    pub fn with_neighbourhood(mut self, neighbourhood: Neighbourhood) -> Self {
+        self.neighbourhood = neighbourhood;
+        self
+    }
+
+    /// Let trails cross the edges, so the map tiles seamlessly
+    pub fn with_wrap(mut self, wrap: bool) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    /// Keep trails and fill inside the mask
+    pub fn with_mask(mut self, mask: Mask) -> Self {
+        self.mask = Some(mask);
+        self
+    }
+
+    pub fn with_fill_strategy(mut self, fill_strategy: FillStrategy) -> Self {
+        self.fill_strategy = fill_strategy;
+        self
+    }
+
+    fn allows(&self, pos: &Position) -> bool {
+        self.mask.as_ref().is_none_or(|mask| mask.allows(pos))
+    }
+
     fn coord(&self, pos: &Position) -> usize {
         (pos.y * self.width) + pos.x
     }
 
-    fn neighbours(&self, pos: &Position, target: char) -> Vec<Position> {
-        let mut neighbours = vec![];
+    fn is_open(&self, pos: &Position, target: u8) -> bool {
+        if !self.allows(pos) {
+            return false;
+        }
+        match self.map[self.coord(pos)] {
+            None => true,
+            Some(height) => height == target,
+        }
+    }
 
-        if pos.y > 0 {
-            let neighbour = Position {
-                x: pos.x,
-                y: pos.y - 1,
-            };
-            if self.map[self.coord(&neighbour)] == '.' || self.map[self.coord(&neighbour)] == target
-            {
-                neighbours.push(neighbour);
+    fn neighbours(&self, pos: &Position, target: u8) -> Vec<Position> {
+        let mut neighbours = self
+            .neighbourhood
+            .neighbours(pos, self.width, self.height, self.wrap);
+        neighbours.retain(|neighbour| self.is_open(neighbour, target));
+        neighbours
+    }
+
+    /// Random walk which climbs one step at a time, backing out of dead
+    /// ends, until the trail is `trail_length` cells long. Cells stay
+    /// visited after backing out so each is only tried once. Returns the
+    /// trail, which is shorter than `trail_length` if growth failed.
+    fn depth_first(&self, start: &Position, rng: &mut SmallRng) -> Vec<Position> {
+        let mut visited = Visited::new(self.map.len());
+        let mut trail = vec![];
+        // neighbours still to try for each cell on the trail
+        let mut stack: Vec<std::vec::IntoIter<Position>> = vec![];
+        let mut next = Some(*start).filter(|start| self.allows(start));
+
+        loop {
+            if let Some(current) = next.take() {
+                trail.push(current);
+                visited.insert(self.coord(&current));
+                if trail.len() == self.trail_length {
+                    return trail;
+                }
+
+                // heights are stored as u8 so trails can't climb any further
+                match u8::try_from(trail.len()) {
+                    Ok(target) => {
+                        let mut neighbours = self.neighbours(&current, target);
+                        neighbours.shuffle(rng);
+                        stack.push(neighbours.into_iter());
+                    }
+                    Err(_) => {
+                        trail.pop();
+                    }
+                }
             }
-        }
-        if pos.y < self.height - 1 {
-            let neighbour = Position {
-                x: pos.x,
-                y: pos.y + 1,
+
+            let Some(neighbours) = stack.last_mut() else {
+                return trail;
             };
-            if self.map[self.coord(&neighbour)] == '.' || self.map[self.coord(&neighbour)] == target
-            {
-                neighbours.push(neighbour);
+            next = neighbours.find(|pos| !visited.contains(self.coord(pos)));
+            if next.is_none() {
+                // dead end, so step back before trying the next neighbour
+                stack.pop();
+                trail.pop();
             }
         }
+    }
 
-        if pos.x < self.width - 1 {
-            let neighbour = Position {
-                x: pos.x + 1,
-                y: pos.y,
-            };
-            if self.map[self.coord(&neighbour)] == '.' || self.map[self.coord(&neighbour)] == target
-            {
-                neighbours.push(neighbour);
-            }
+    /// Grow a trail from `start`, unless it can't reach `trail_length`
+    /// without crossing another trail. Returns whether it grew.
+    pub fn add_trail(&mut self, start: &Position, rng: &mut SmallRng) -> bool {
+        let trail = self.depth_first(start, rng);
+        if trail.len() != self.trail_length {
+            return false;
         }
-        if pos.x > 0 {
-            let neighbour = Position {
-                x: pos.x - 1,
-                y: pos.y,
-            };
-            if self.map[self.coord(&neighbour)] == '.' || self.map[self.coord(&neighbour)] == target
-            {
-                neighbours.push(neighbour);
-            }
+        // update map
+        for (height, point) in trail.iter().enumerate() {
+            let coord = self.coord(point);
+            self.map[coord] = Some(height as u8);
         }
-        neighbours
+        true
+    }
+
+    /// Grow a trail from each starting point, skipping any which can't reach
+    /// `trail_length` without crossing another trail. Returns whether each
+    /// one grew.
+    pub fn add_trails(&mut self, starting_points: &[Position], rng: &mut SmallRng) -> Vec<bool> {
+        starting_points
+            .iter()
+            .map(|pos| self.add_trail(pos, rng))
+            .collect()
     }
 
-    fn depth_first(
-        &self,
-        visited: &mut Vec<Position>,
-        trail: &mut Vec<Position>,
-        current: &Position,
-        mut rng: &mut SmallRng,
-    ) -> Vec<Position> {
-        let current_val = trail.len();
-        trail.push(*current);
-        visited.push(*current);
+    /// As `add_trails`, but starts which fail are retried elsewhere in
+    /// their leaf of `root`, and with a target more starts are tried in
+    /// random leaves until enough trails are planted or the budget runs out
+    pub fn grow(
+        &mut self,
+        starting_points: &[Position],
+        root: &Leaf,
+        rules: &GrowthRules,
+        rng: &mut SmallRng,
+    ) -> GrowthReport {
+        let mut report = GrowthReport {
+            requested: starting_points.len(),
+            starts: vec![],
+        };
+        let budget = rules.budget();
+        let reached = |report: &GrowthReport| {
+            report.starts.len() >= budget
+                || rules
+                    .target
+                    .is_some_and(|target| report.planted() >= target)
+        };
+
+        // starts which failed, which won't grow once more trails are planted
+        let mut failed = vec![false; self.map.len()];
+        let mut leaves = root.leaves();
+        for start in starting_points {
+            if reached(&report) {
+                return report;
+            }
+            let leaf = leaves.iter().find(|leaf| leaf.contains(start)).copied();
+            self.grow_from(*start, leaf, rules, &mut failed, &mut report, rng);
+        }
 
-        if trail.len() == 10 {
-            return trail.to_vec();
+        if rules.target.is_some() {
+            // every pick either tries a start or drops a leaf with nothing
+            // left to try, so this ends once the canvas is used up
+            while !reached(&report) && !leaves.is_empty() {
+                let index = rng.random_range(0..leaves.len());
+                let leaf = leaves[index];
+                match self.free_cell(leaf, &failed, rng) {
+                    Some(start) => {
+                        self.grow_from(start, Some(leaf), rules, &mut failed, &mut report, rng)
+                    }
+                    None => {
+                        leaves.swap_remove(index);
+                    }
+                }
+            }
         }
-        let target = char::from_digit(current_val as u32 + 1, 10).expect("failed to parse num");
-        let mut neighbours = self.neighbours(current, target);
-        neighbours.shuffle(&mut rng);
+        report
+    }
 
-        for pos in neighbours {
-            if !visited.contains(&pos) && current_val < 9 && trail.len() < 10 {
-                self.depth_first(visited, trail, &pos, rng);
+    /// Try `start`, then up to `retries` other free cells of `leaf` until
+    /// one grows a trail
+    fn grow_from(
+        &mut self,
+        mut start: Position,
+        leaf: Option<&Leaf>,
+        rules: &GrowthRules,
+        failed: &mut [bool],
+        report: &mut GrowthReport,
+        rng: &mut SmallRng,
+    ) {
+        for retry in 0..=rules.retries {
+            if report.starts.len() >= rules.budget() {
+                return;
+            }
+            if retry > 0 {
+                let Some(cell) = leaf.and_then(|leaf| self.free_cell(leaf, failed, rng)) else {
+                    return;
+                };
+                start = cell;
             }
+            let grown = self.add_trail(&start, rng);
+            report.starts.push((start, grown));
+            if grown {
+                return;
+            }
+            failed[self.coord(&start)] = true;
         }
+    }
 
-        trail.to_vec()
+    /// Random cell of `leaf` which isn't masked, on a trail or a start
+    /// which already failed
+    fn free_cell(&self, leaf: &Leaf, failed: &[bool], rng: &mut SmallRng) -> Option<Position> {
+        let cells: Vec<Position> = leaf
+            .cells()
+            .into_iter()
+            .filter(|pos| {
+                let index = self.coord(pos);
+                self.allows(pos) && self.map[index].is_none() && !failed[index]
+            })
+            .collect();
+        cells.choose(rng).copied()
     }
 
-    pub fn add_trails(&mut self, starting_points: &[Position], rng: &mut SmallRng) {
-        let mut trails = vec![];
+    /// Give every empty cell inside the mask a height
+    pub fn fill(&mut self, rng: &mut SmallRng) {
+        match self.fill_strategy {
+            FillStrategy::Random => self.fill_random(rng),
+            FillStrategy::Safe => self.fill_safe(false, rng),
+            FillStrategy::NearMiss => self.fill_safe(true, rng),
+        }
+    }
 
-        for pos in starting_points.iter() {
-            let mut visited = vec![];
-            let mut trail = vec![];
+    fn position(&self, index: usize) -> Position {
+        Position {
+            x: index % self.width,
+            y: index / self.width,
+        }
+    }
 
-            trails.push(self.depth_first(&mut visited, &mut trail, pos, rng));
-            if trail.len() == 10 {
-                // update map
-                for (i, point) in trail.iter().enumerate() {
-                    let coord = self.coord(point);
-                    self.map[coord] = char::from_digit(i as u32, 10).expect("Couldn't parse index");
-                }
+    fn fill_random(&mut self, rng: &mut SmallRng) {
+        for index in 0..self.map.len() {
+            if self.map[index].is_none() && self.allows(&self.position(index)) {
+                self.map[index] = Some(rng.random_range(0..self.trail_length) as u8);
             }
-            trail.truncate(0);
         }
     }
 
-    pub fn fill(&mut self, rng: &mut SmallRng) {
-        self.map = self
-            .map
-            .iter()
-            .map(|i| {
-                if *i == '.' {
-                    return char::from_digit(rng.random_range(0..=9), 10)
-                        .expect("Couldn't parse number");
-                }
-                *i
+    fn fill_safe(&mut self, near_miss: bool, rng: &mut SmallRng) {
+        let open: Vec<bool> = (0..self.map.len())
+            .map(|index| self.allows(&self.position(index)))
+            .collect();
+        let adjacent: Vec<Vec<usize>> = (0..self.map.len())
+            .map(|index| {
+                self.neighbourhood
+                    .neighbours(&self.position(index), self.width, self.height, self.wrap)
+                    .iter()
+                    .map(|pos| self.coord(pos))
+                    .filter(|next| open[*next])
+                    .collect()
             })
-            .collect()
+            .collect();
+        fill_safe(
+            &mut self.map,
+            &adjacent,
+            &open,
+            self.trail_length,
+            near_miss,
+            rng,
+        );
     }
 }
 
 impl Display for Input {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.map.iter().enumerate().for_each(|(index, item)| {
-            write!(f, "{}", item).expect("Failed to write item");
+        // right align separated heights so columns line up
+        let cell_width = match self.alphabet {
+            Alphabet::Separated => self.trail_length.saturating_sub(1).to_string().len(),
+            _ => 1,
+        };
+
+        for (index, item) in self.map.iter().enumerate() {
+            let cell = match item {
+                Some(height) => self.alphabet.encode(*height),
+                None => String::from("."),
+            };
+            if index % self.width != 0 && self.alphabet == Alphabet::Separated {
+                write!(f, " ")?;
+            }
+            write!(f, "{cell:>cell_width$}")?;
             if (index + 1) % self.width == 0 {
-                writeln!(f).expect("Failed to add new line");
+                writeln!(f)?;
             }
-        });
+        }
         Ok(())
     }
 }
@@ -148,7 +407,22 @@ impl Display for Input {
 mod test {
     use rand::{SeedableRng, rngs::SmallRng};
 
-    use crate::{input::Input, map::Position};
+    use crate::{
+        alphabet::Alphabet,
+        fill::FillStrategy,
+        input::{GrowthRules, Input},
+        map::{Map, Position},
+        mask::Shape,
+        quadtree::Leaf,
+        topology::Neighbourhood,
+    };
+
+    fn cells(chars: &[char]) -> Vec<Option<u8>> {
+        chars
+            .iter()
+            .map(|c| c.to_digit(10).map(|height| height as u8))
+            .collect()
+    }
 
     #[test]
     fn it_should_format_correctly() {
@@ -165,6 +439,15 @@ mod test {
         );
     }
 
+    #[test]
+    fn it_should_format_rectangle() {
+        let input = Input::new(5, 2);
+        assert_eq!(format!("{}", input), ".....\n.....\n");
+
+        let input = Input::new(2, 3);
+        assert_eq!(format!("{}", input), "..\n..\n..\n");
+    }
+
     #[test]
     fn it_should_add_trails() {
         let mut input = Input::new(6, 6);
@@ -185,11 +468,17 @@ mod test {
             Input {
                 width: 6,
                 height: 6,
-                map: vec![
-                    '1', '0', '.', '0', '1', '.', '2', '.', '9', '.', '2', '.', '3', '4', '8', '4',
-                    '3', '9', '8', '5', '7', '5', '6', '8', '7', '6', '0', '1', '6', '7', '9', '4',
-                    '3', '2', '5', '.'
-                ]
+                trail_length: 10,
+                alphabet: Alphabet::Digits,
+                neighbourhood: Neighbourhood::VonNeumann,
+                wrap: false,
+                mask: None,
+                fill_strategy: FillStrategy::Random,
+                map: cells(&[
+                    '1', '0', '.', '.', '.', '9', '2', '.', '.', '8', '9', '8', '3', '4', '.', '7',
+                    '6', '7', '.', '5', '.', '4', '5', '6', '7', '6', '0', '3', '2', '1', '8', '9',
+                    '1', '2', '.', '0'
+                ])
             }
         );
     }
@@ -215,16 +504,189 @@ mod test {
             Input {
                 width: 10,
                 height: 10,
-                map: vec![
-                    '1', '0', '5', '0', '7', '8', '9', '2', '4', '4', '2', '6', '0', '1', '6', '5',
-                    '9', '1', '6', '2', '3', '4', '0', '2', '3', '4', '8', '4', '7', '3', '8', '5',
-                    '9', '8', '5', '6', '7', '6', '1', '1', '7', '6', '0', '1', '4', '9', '8', '5',
-                    '1', '8', '9', '2', '1', '2', '3', '0', '1', '4', '6', '5', '6', '7', '7', '5',
-                    '9', '6', '2', '3', '7', '0', '8', '1', '4', '9', '6', '8', '1', '4', '7', '5',
-                    '2', '4', '1', '1', '2', '9', '2', '1', '3', '4', '6', '4', '2', '2', '4', '5',
-                    '1', '1', '4', '3'
-                ]
+                trail_length: 10,
+                alphabet: Alphabet::Digits,
+                neighbourhood: Neighbourhood::VonNeumann,
+                wrap: false,
+                mask: None,
+                fill_strategy: FillStrategy::Random,
+                map: cells(&[
+                    '1', '0', '2', '4', '4', '9', '6', '0', '1', '6', '2', '2', '0', '4', '7', '8',
+                    '3', '9', '8', '1', '3', '4', '1', '1', '8', '7', '2', '1', '6', '5', '6', '5',
+                    '7', '4', '5', '6', '7', '5', '9', '6', '7', '6', '0', '3', '2', '3', '4', '7',
+                    '0', '8', '8', '9', '1', '2', '1', '0', '5', '6', '1', '4', '9', '6', '8', '1',
+                    '4', '7', '8', '7', '5', '2', '4', '1', '1', '2', '9', '2', '9', '1', '3', '4',
+                    '6', '4', '2', '2', '4', '5', '1', '1', '4', '3', '1', '9', '2', '4', '7', '3',
+                    '9', '3', '6', '5'
+                ])
             }
         );
     }
+
+    #[test]
+    fn it_should_round_trip_long_trails() {
+        let seed = 123;
+        let mut rng = SmallRng::seed_from_u64(seed);
+
+        for (trail_length, alphabet) in [
+            (4, Alphabet::Digits),
+            (30, Alphabet::Base36),
+            (40, Alphabet::Separated),
+        ] {
+            let mut input = Input::new(12, 9).with_trail_length(trail_length);
+            assert_eq!(input.alphabet, alphabet);
+
+            input.add_trails(&[Position { x: 3, y: 3 }], &mut rng);
+            input.fill(&mut rng);
+
+            let text = format!("{}", input);
+            let mut map = Map::parse_with(&text, alphabet).expect("Failed to parse");
+            assert_eq!(
+                map.map,
+                input.map.iter().flatten().copied().collect::<Vec<u8>>()
+            );
+
+            map.find_all_paths(trail_length).expect("Too many paths");
+            assert!(!map.paths.is_empty());
+            assert!(map.paths.iter().all(|path| path.len() == trail_length));
+        }
+    }
+
+    #[test]
+    fn it_should_format_separated() {
+        let mut input = Input::new(3, 2)
+            .with_trail_length(12)
+            .with_alphabet(Alphabet::Separated);
+        input.map = vec![Some(0), Some(11), None, Some(3), Some(10), Some(9)];
+
+        assert_eq!(format!("{}", input), " 0 11  .\n 3 10  9\n");
+    }
+
+    #[test]
+    fn it_should_stay_inside_mask() {
+        let mask = Shape::grid("##..\n##..\n####").expect("Invalid mask");
+        let mut input = Input::new(4, 3)
+            .with_trail_length(4)
+            .with_mask(mask.mask(4, 3));
+        let mut rng = SmallRng::seed_from_u64(123);
+
+        // the first start is outside the mask so doesn't grow a trail
+        input.add_trails(
+            &[Position { x: 3, y: 0 }, Position { x: 0, y: 0 }],
+            &mut rng,
+        );
+        assert_eq!(input.map[3], None);
+        assert_eq!(input.map[0], Some(0));
+
+        input.fill(&mut rng);
+        assert_eq!(format!("{}", input).matches('.').count(), 4);
+        assert_eq!(input.map[2..4], [None, None]);
+        assert_eq!(input.map[6..8], [None, None]);
+    }
+
+    #[test]
+    fn it_should_retry_and_reach_target() {
+        let mask = Shape::grid("##..\n##..\n####").expect("Invalid mask");
+        let root = Leaf::new(0, 0, 4, 3, 1, 0);
+        let outside = Position { x: 3, y: 0 };
+        let mut rng = SmallRng::seed_from_u64(123);
+
+        let mut input = Input::new(4, 3)
+            .with_trail_length(4)
+            .with_mask(mask.mask(4, 3));
+        let report = input.grow(&[outside], &root, &GrowthRules::default(), &mut rng);
+        assert_eq!((report.requested, report.planted()), (1, 0));
+        assert_eq!(report.starts, vec![(outside, false)]);
+
+        // another cell of the leaf is tried instead
+        let rules = GrowthRules {
+            retries: 3,
+            ..GrowthRules::default()
+        };
+        let report = input.grow(&[outside], &root, &rules, &mut rng);
+        assert_eq!(report.planted(), 1);
+        assert_eq!(report.starts[0], (outside, false));
+        assert!(
+            report
+                .starts
+                .iter()
+                .skip(1)
+                .all(|(pos, _)| mask.mask(4, 3).allows(pos))
+        );
+
+        let root = Leaf::new(0, 0, 12, 12, 3, 0);
+        let rules = GrowthRules {
+            target: Some(6),
+            ..GrowthRules::default()
+        };
+        let mut input = Input::new(12, 12).with_trail_length(4);
+        let report = input.grow(&[], &root, &rules, &mut rng);
+        assert_eq!(report.planted(), 6);
+        assert!(report.starts.last().is_some_and(|(_, grown)| *grown));
+
+        // runs out of attempts long before filling the canvas
+        let rules = GrowthRules {
+            target: Some(100),
+            budget: Some(5),
+            ..GrowthRules::default()
+        };
+        let report = input.grow(&[], &root, &rules, &mut rng);
+        assert!(report.starts.len() <= 5);
+        assert!(report.planted() < 100);
+
+        // stops once no cell is left to try, rather than using the budget
+        let rules = GrowthRules {
+            target: Some(1_000_000),
+            ..GrowthRules::default()
+        };
+        let report = input.grow(&[], &root, &rules, &mut rng);
+        assert!(report.starts.len() < 144);
+        assert!((0..144).all(|index| {
+            let pos = Position {
+                x: index % 12,
+                y: index / 12,
+            };
+            input.map[index].is_some() || report.starts.contains(&(pos, false))
+        }));
+    }
+
+    #[test]
+    fn it_should_only_find_planted_trails() {
+        let found = |input: &Input| {
+            let mut map = Map::parse(&format!("{}", input))
+                .expect("Failed to parse")
+                .with_neighbourhood(input.neighbourhood);
+            map.find_all_paths(10).expect("Too many paths");
+            map.paths
+        };
+
+        for (strategy, neighbourhood) in [
+            (FillStrategy::Random, Neighbourhood::VonNeumann),
+            (FillStrategy::Safe, Neighbourhood::VonNeumann),
+            (FillStrategy::NearMiss, Neighbourhood::VonNeumann),
+            (FillStrategy::Safe, Neighbourhood::Moore),
+            (FillStrategy::NearMiss, Neighbourhood::Hex),
+        ] {
+            let mut rng = SmallRng::seed_from_u64(123);
+            let mut input = Input::new(30, 30)
+                .with_neighbourhood(neighbourhood)
+                .with_fill_strategy(strategy);
+            let starting_points: Vec<Position> = (0..30)
+                .map(|index| Position {
+                    x: index * 7 % 30,
+                    y: index * 11 % 30,
+                })
+                .collect();
+            input.add_trails(&starting_points, &mut rng);
+            // blank cells parse as masked, so only planted trails are found
+            let planted = found(&input);
+            assert!(!planted.is_empty());
+
+            input.fill(&mut rng);
+            match strategy {
+                FillStrategy::Random => assert!(found(&input).len() > planted.len()),
+                _ => assert_eq!(found(&input), planted),
+            }
+        }
+    }
 }
@@ -1,34 +1,413 @@
-use input::Input;
-use map::Map;
-use quadtree::Leaf;
+use config::GenerationConfig;
+use error::Error;
+use input::{GrowthReport, Input};
+use map::{Map, Position};
+use order::PathOrder;
+use placement::StartPlacement;
 use rand::rngs::SmallRng;
 use rand_seeder::Seeder;
-use svg::Svg;
+use select::select_paths;
+use svg::{Overlay, Svg};
 
+pub mod alphabet;
+pub mod config;
+pub mod curve;
+pub mod error;
+pub mod fill;
+pub mod graph;
 pub mod input;
 pub mod map;
+pub mod mask;
+pub mod order;
+pub mod palette;
+pub mod partition;
+pub mod placement;
+pub mod plot;
 pub mod quadtree;
+#[cfg(feature = "png")]
+pub mod raster;
+pub mod select;
 pub mod svg;
+pub mod topology;
 
-pub fn create(seed: &String, canvas_size: usize, min_leaf_size: usize, density: u8) -> String {
-    let mut rng: SmallRng = Seeder::from(&seed).into_rng();
+/// Output of a single run through the pipeline
+#[derive(Debug)]
+pub struct Generation {
+    pub svg: String,
+    pub map: Map,
+    pub starting_points: Vec<Position>,
+    /// Which starts grew trails, including retries
+    pub growth: GrowthReport,
+    /// Drawing order of `map.paths`, or of merged strokes, and how much pen
+    /// up travel it saves
+    pub order: PathOrder,
+}
+
+pub fn generate(config: &GenerationConfig) -> Result<Generation, Error> {
+    config.validate()?;
+
+    let mut rng: SmallRng = Seeder::from(&config.seed).into_rng();
 
-    let width = canvas_size;
-    let height = canvas_size;
-    let depth = 0;
-    let mut root = Leaf::new(0, 0, width, height, min_leaf_size, depth);
-    root.generate(&mut rng);
+    let root = config.partition.partitioner(config.split).partition(
+        config.width,
+        config.height,
+        config.min_leaf_size,
+        &mut rng,
+    );
 
-    let mut input = Input::new(width, height);
-    let mut starting_points = vec![];
-    root.add_start(&mut starting_points, &mut rng, density);
+    let alphabet = config.alphabet();
+    let mut input = Input::new(config.width, config.height)
+        .with_trail_length(config.trail_length)
+        .with_alphabet(alphabet)
+        .with_neighbourhood(config.neighbourhood)
+        .with_wrap(config.wrap)
+        .with_fill_strategy(config.fill);
+    let mask = config
+        .mask
+        .as_ref()
+        .map(|shape| shape.mask(config.width, config.height));
+    let starting_points = config
+        .placement
+        .place(&root, config.density, mask.as_ref(), &mut rng);
+    if let Some(mask) = mask {
+        input = input.with_mask(mask);
+    }
 
-    input.add_trails(&starting_points, &mut rng);
+    let growth = input.grow(&starting_points, &root, &config.growth, &mut rng);
     input.fill(&mut rng);
 
-    let mut map = Map::parse(&format!("{}", input));
-    map.find_all_paths();
+    let mut map = Map::parse_with(&format!("{}", input), alphabet)?
+        .with_neighbourhood(config.neighbourhood)
+        .with_wrap(config.wrap);
+    map.paths = select_paths(
+        &map,
+        config.trail_length,
+        config.selection,
+        config.max_trails,
+        &config.seed,
+    )?;
+
+    let trail_colours = match &config.style.palette {
+        Some(palette) => palette.trail_colours(
+            &map,
+            config.style.colour_strategy,
+            Some(&root),
+            &config.seed,
+        ),
+        None => vec![],
+    };
+    let mut svg = Svg::from_style(&config.style, map.width, map.height)
+        .with_neighbourhood(map.neighbourhood)
+        .with_wrap(map.wrap)
+        .with_trail_colours(trail_colours);
+    if config.style.debug_overlay {
+        svg = svg.with_overlay(Overlay::new(&root, &growth.starts));
+    }
+    let order = svg.path_order(&map, config.style.ordering);
+    let svg = svg.with_order(order.order.clone()).draw(&map)?;
+
+    Ok(Generation {
+        svg,
+        map,
+        starting_points,
+        growth,
+        order,
+    })
+}
+
+/// Square canvas with the default style. Prefer `generate` for anything else.
+pub fn create(
+    seed: &str,
+    canvas_size: usize,
+    min_leaf_size: usize,
+    density: u8,
+) -> Result<String, Error> {
+    let config = GenerationConfig::builder(seed)
+        .canvas_size(canvas_size)
+        .min_leaf_size(min_leaf_size)
+        .density(density)
+        .build()?;
+
+    Ok(generate(&config)?.svg)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use crate::{
+        config::{GenerationConfig, Style},
+        create, generate,
+        mask::Shape,
+        order::PathOrdering,
+        palette::{ColourStrategy, Palette},
+        select::Selection,
+        svg::Svg,
+        topology::Neighbourhood,
+    };
+
+    #[test]
+    fn it_should_match_create() {
+        let seed = String::from("trails");
+        let config = GenerationConfig::builder(&seed)
+            .canvas_size(20)
+            .min_leaf_size(3)
+            .density(2)
+            .build()
+            .expect("Invalid config");
+        let generation = generate(&config).expect("Failed to generate");
+
+        assert_eq!(Ok(generation.svg), create(&seed, 20, 3, 2));
+        assert_eq!(generation.map.width, 20);
+        assert_eq!(generation.map.height, 20);
+        assert!(generation.map.paths.iter().all(|path| path.len() == 10));
+    }
+
+    #[test]
+    fn it_should_generate_rectangle() {
+        let config = GenerationConfig::builder("trails")
+            .width(30)
+            .height(12)
+            .build()
+            .expect("Invalid config");
+        let generation = generate(&config).expect("Failed to generate");
+
+        assert_eq!(generation.map.width, 30);
+        assert_eq!(generation.map.height, 12);
+        assert!(!generation.map.paths.is_empty());
+        assert!(
+            generation
+                .map
+                .paths
+                .iter()
+                .flatten()
+                .all(|pos| pos.x < 30 && pos.y < 12)
+        );
+        assert!(generation.svg.starts_with("<svg viewBox=\"0 0 960 384\""));
+    }
+
+    #[test]
+    fn it_should_colour_trails_from_palette() {
+        let config = GenerationConfig::builder("trails")
+            .canvas_size(20)
+            .style(Style {
+                palette: Palette::named("ocean"),
+                colour_strategy: ColourStrategy::PathIndex,
+                ..Style::default()
+            })
+            .build()
+            .expect("Invalid config");
+        let generation = generate(&config).expect("Failed to generate");
+
+        assert!(generation.map.paths.len() > 4);
+        assert!(!generation.svg.contains("stroke=\"black\""));
+        for colour in ["#03045e", "#0077b6", "#00b4d8", "#90e0ef"] {
+            assert!(generation.svg.contains(&format!("stroke=\"{colour}\"")));
+        }
+    }
+
+    #[test]
+    fn it_should_use_trail_length() {
+        for trail_length in [4, 5, 30] {
+            let config = GenerationConfig::builder("trails")
+                .canvas_size(20)
+                .trail_length(trail_length)
+                .build()
+                .expect("Invalid config");
+            let generation = generate(&config).expect("Failed to generate");
+
+            assert!(!generation.map.paths.is_empty());
+            assert!(
+                generation
+                    .map
+                    .paths
+                    .iter()
+                    .all(|path| path.len() == trail_length)
+            );
+        }
+    }
+
+    #[test]
+    fn it_should_select_trails() {
+        let builder = GenerationConfig::builder("trails").canvas_size(30);
+        let all = generate(&builder.clone().build().expect("Invalid config"))
+            .expect("Failed to generate");
+        let config = builder
+            .selection(Selection::Trailhead)
+            .max_trails(3)
+            .build()
+            .expect("Invalid config");
+        let selected = generate(&config).expect("Failed to generate");
+
+        assert!(all.map.paths.len() > 3);
+        assert_eq!(selected.map.paths.len(), 3);
+        assert!(
+            selected
+                .map
+                .paths
+                .iter()
+                .all(|path| all.map.paths.contains(path))
+        );
+    }
+
+    #[test]
+    fn it_should_order_for_travel() {
+        let config = |ordering| {
+            GenerationConfig::builder("trails")
+                .canvas_size(30)
+                .style(Style {
+                    ordering,
+                    ..Style::default()
+                })
+                .build()
+                .expect("Invalid config")
+        };
+        let scan = generate(&config(PathOrdering::Scan)).expect("Failed to generate");
+        let travel = generate(&config(PathOrdering::Reversible)).expect("Failed to generate");
+
+        assert_eq!(scan.order.before, travel.order.before);
+        assert!(travel.order.after < travel.order.before);
+        assert_ne!(scan.svg, travel.svg);
+        assert_eq!(
+            scan.svg.matches("<path").count(),
+            travel.svg.matches("<path").count()
+        );
+    }
+
+    #[test]
+    fn it_should_step_diagonally() {
+        let config = |neighbourhood| {
+            GenerationConfig::builder("trails")
+                .canvas_size(20)
+                .neighbourhood(neighbourhood)
+                .build()
+                .expect("Invalid config")
+        };
+        let square = generate(&config(Neighbourhood::VonNeumann)).expect("Failed to generate");
+        let diagonal = generate(&config(Neighbourhood::Moore)).expect("Failed to generate");
+
+        // path data with an `l` command
+        let diagonals = |svg: &str| {
+            svg.split(" d=\"")
+                .skip(1)
+                .filter(|rest| rest.split('"').next().is_some_and(|d| d.contains('l')))
+                .count()
+        };
+        assert_eq!(diagonals(&square.svg), 0);
+        assert!(diagonals(&diagonal.svg) > 0);
+        assert!(diagonal.map.paths.iter().any(|path| {
+            path.windows(2)
+                .any(|step| step[0].x != step[1].x && step[0].y != step[1].y)
+        }));
+    }
+
+    #[test]
+    fn it_should_tile_seamlessly() {
+        let config = GenerationConfig::builder("trails")
+            .canvas_size(20)
+            .density(4)
+            .wrap(true)
+            .build()
+            .expect("Invalid config");
+        let generation = generate(&config).expect("Failed to generate");
+        assert!(generation.svg.starts_with("<svg viewBox=\"0 0 640 640\""));
+
+        let shapes = Svg::from_style(&config.style, 20, 20)
+            .with_wrap(true)
+            .shapes(&generation.map)
+            .expect("Failed to draw");
+        assert!(shapes.len() > generation.map.paths.len());
+        let points = shapes.iter().flat_map(|shape| &shape.path);
+        assert!(
+            points
+                .clone()
+                .all(|(x, y)| (0.0..=640.0).contains(x) && (0.0..=640.0).contains(y))
+        );
+
+        // stitch 2x2 copies together, and every line ending on a join should
+        // meet one from the tile next to it
+        let mut seams: BTreeMap<(i64, i64), usize> = BTreeMap::new();
+        for (x, y) in [(0.0, 0.0), (640.0, 0.0), (0.0, 640.0), (640.0, 640.0)] {
+            for shape in &shapes {
+                for end in [shape.path[0], shape.path[shape.path.len() - 1]] {
+                    let end = (end.0 + x, end.1 + y);
+                    let inside = |val: f64| val > 0.0 && val < 1280.0;
+                    if (end.0 == 640.0 || end.1 == 640.0) && inside(end.0) && inside(end.1) {
+                        *seams.entry((end.0 as i64, end.1 as i64)).or_default() += 1;
+                    }
+                }
+            }
+        }
+        assert!(!seams.is_empty());
+        assert!(seams.values().all(|count| count % 2 == 0));
+    }
+
+    #[test]
+    fn it_should_keep_trails_inside_mask() {
+        let config = GenerationConfig::builder("trails")
+            .canvas_size(30)
+            .density(4)
+            .mask(Shape::Circle)
+            .build()
+            .expect("Invalid config");
+        let generation = generate(&config).expect("Failed to generate");
+        let mask = Shape::Circle.mask(30, 30);
+
+        assert!(!generation.map.paths.is_empty());
+        assert!(
+            generation
+                .starting_points
+                .iter()
+                .chain(generation.map.paths.iter().flatten())
+                .all(|pos| mask.allows(pos))
+        );
+        assert_eq!(generation.map.mask, Some(mask));
+    }
+
+    #[test]
+    fn it_should_draw_debug_overlay() {
+        let config = GenerationConfig::builder("trails")
+            .canvas_size(20)
+            .density(4)
+            .style(Style {
+                debug_overlay: true,
+                ..Style::default()
+            })
+            .build()
+            .expect("Invalid config");
+        let generation = generate(&config).expect("Failed to generate");
+
+        let grown = generation.svg.matches("fill=\"#2e8b57\"").count();
+        let failed = generation.svg.matches("stroke=\"#dc143c\"").count();
+        assert!(grown > 0 && failed > 0);
+        assert_eq!(grown + failed, generation.starting_points.len());
+        assert!(generation.svg.contains("<g id=\"leaves\""));
+    }
+
+    #[test]
+    fn it_should_report_trails_planted() {
+        let builder = GenerationConfig::builder("trails")
+            .canvas_size(20)
+            .density(4);
+        let config = builder.clone().build().expect("Invalid config");
+        let generation = generate(&config).expect("Failed to generate");
+        let growth = &generation.growth;
+        assert_eq!(growth.requested, generation.starting_points.len());
+        assert_eq!(growth.starts.len(), growth.requested);
+        assert!(growth.planted() < growth.requested);
+
+        let config = builder.retries(5).build().expect("Invalid config");
+        let retried = generate(&config).expect("Failed to generate").growth;
+        assert!(retried.planted() > growth.planted());
 
-    let svg = Svg::new(32, 16, map.width, map.height, 2, "black", 10);
-    svg.draw(&map)
+        let config = GenerationConfig::builder("trails")
+            .canvas_size(20)
+            .density(1)
+            .target_trails(30)
+            .build()
+            .expect("Invalid config");
+        let generation = generate(&config).expect("Failed to generate");
+        assert_eq!(generation.growth.planted(), 30);
+        assert!(generation.growth.starts.len() <= 300);
+    }
 }
@@ -1,6 +1,16 @@
-use std::collections::VecDeque;
+use std::cmp::Reverse;
 
-#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+use crate::{
+    alphabet::Alphabet,
+    error::{Diagnostic, DiagnosticKind, Error},
+    mask::Mask,
+    topology::Neighbourhood,
+};
+
+/// Most routes `Map::find_all_paths` will hold in memory at once
+pub const MAX_PATHS: usize = 1_000_000;
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
 pub struct Position {
     pub x: usize,
     pub y: usize,
@@ -13,149 +23,415 @@ pub struct Map {
     pub height: usize,
     pub trailheads: Vec<Position>,
     pub paths: Vec<Vec<Position>>,
+    /// Cells a trail can step to
+    pub neighbourhood: Neighbourhood,
+    /// Trails can step off one edge and back on at the opposite one
+    pub wrap: bool,
+    /// Cells written as `.`, which trails can't use
+    pub mask: Option<Mask>,
 }
 impl Map {
-    pub fn parse(input: &str) -> Self {
-        let mut height = 0;
+    /// Parse rows of digits, with `.` for cells outside the mask. Leading
+    /// indentation is ignored, anything else which isn't a digit is reported
+    /// with its line and column.
+    pub fn parse(input: &str) -> Result<Self, Error> {
+        Map::parse_with(input, Alphabet::Digits)
+    }
+
+    /// Parse rows of heights written with `alphabet`
+    pub fn parse_with(input: &str, alphabet: Alphabet) -> Result<Self, Error> {
+        if input.trim().is_empty() {
+            return Err(Error::EmptyInput);
+        }
+
         let mut trailheads = vec![];
+        let mut map: Vec<u8> = vec![];
+        let mut allowed = vec![];
+        let mut width = None;
+        let mut height = 0;
+        let mut diagnostics = vec![];
+
+        let mut lines: Vec<&str> = input.split('\n').collect();
+        if input.ends_with('\n') {
+            lines.pop();
+        }
+
+        for (line_index, raw) in lines.iter().enumerate() {
+            let line = line_index + 1;
+
+            let mut content = *raw;
+            if let Some(stripped) = content.strip_suffix('\r') {
+                diagnostics.push(Diagnostic {
+                    line,
+                    column: stripped.chars().count() + 1,
+                    kind: DiagnosticKind::CarriageReturn,
+                });
+                content = stripped;
+            }
+
+            let row = content.trim();
+            if row.is_empty() {
+                diagnostics.push(Diagnostic {
+                    line,
+                    column: 1,
+                    kind: DiagnosticKind::BlankLine,
+                });
+                continue;
+            }
+
+            let indent = content.chars().count() - content.trim_start().chars().count();
+            let cells = alphabet.cells(row);
+            if content.trim_end().len() != content.len() {
+                diagnostics.push(Diagnostic {
+                    line,
+                    column: indent + row.chars().count() + 1,
+                    kind: DiagnosticKind::TrailingWhitespace,
+                });
+            }
 
-        let map: Vec<u8> = input
-            .lines()
-            .flat_map(|line| {
-                let row = line
-                    .trim()
-                    .chars()
-                    .enumerate()
-                    .map(|(index, n)| {
-                        let val = n.to_digit(10).expect("Not a number") as u8;
-                        if val == 0 {
-                            trailheads.push(Position {
-                                x: index,
-                                y: height,
-                            });
-                        }
-                        val
+            match width {
+                None => width = Some(cells.len()),
+                Some(expected) if expected != cells.len() => {
+                    // point at the first missing or extra cell
+                    let column = match cells.get(expected) {
+                        Some((column, _)) => *column,
+                        None => row.chars().count(),
+                    };
+                    diagnostics.push(Diagnostic {
+                        line,
+                        column: indent + column + 1,
+                        kind: DiagnosticKind::RaggedRow {
+                            expected,
+                            found: cells.len(),
+                        },
                     })
-                    .collect::<Vec<_>>();
-                height += 1;
-                row
-            })
-            .collect();
-        let width = map.len() / height;
+                }
+                _ => {}
+            }
+
+            for (index, (column, cell)) in cells.iter().enumerate() {
+                if *cell == "." {
+                    // blocked cells are never trailheads or climbed to
+                    allowed.push(false);
+                    map.push(0);
+                    continue;
+                }
+                let Some(val) = alphabet.decode(cell) else {
+                    let mut chars = cell.chars();
+                    let kind = match (chars.next(), chars.next()) {
+                        (Some(c), None) => DiagnosticKind::InvalidChar(c),
+                        _ => DiagnosticKind::InvalidValue(cell.to_string()),
+                    };
+                    diagnostics.push(Diagnostic {
+                        line,
+                        column: indent + column + 1,
+                        kind,
+                    });
+                    continue;
+                };
+                if val == 0 {
+                    trailheads.push(Position {
+                        x: index,
+                        y: height,
+                    });
+                }
+                allowed.push(true);
+                map.push(val);
+            }
+            height += 1;
+        }
 
-        Map {
+        if !diagnostics.is_empty() {
+            return Err(Error::Parse(diagnostics));
+        }
+
+        let width = width.unwrap_or(0);
+        let mask = allowed.contains(&false).then_some(Mask {
+            width,
+            cells: allowed,
+        });
+        Ok(Map {
             width,
             height,
             map,
             trailheads,
             paths: vec![],
-        }
+            neighbourhood: Neighbourhood::default(),
+            wrap: false,
+            mask,
+        })
+    }
+
+    /// Follow trails which step diagonally too
+    pub fn with_neighbourhood(mut self, neighbourhood: Neighbourhood) -> Self {
+        self.neighbourhood = neighbourhood;
+        self
+    }
+
+    /// Let trails cross the edges, so the map tiles seamlessly
+    pub fn with_wrap(mut self, wrap: bool) -> Self {
+        self.wrap = wrap;
+        self
     }
 
     fn coord(&self, pos: &Position) -> usize {
         (pos.y * self.width) + pos.x
     }
 
+    fn allows(&self, pos: &Position) -> bool {
+        self.mask.as_ref().is_none_or(|mask| mask.allows(pos))
+    }
+
     fn neighbours(&self, pos: &Position) -> Vec<Position> {
-        let mut neighbours = vec![];
+        let mut neighbours = self
+            .neighbourhood
+            .neighbours(pos, self.width, self.height, self.wrap);
+        neighbours.retain(|neighbour| self.allows(neighbour));
+        neighbours
+    }
 
-        if pos.y > 0 {
-            neighbours.push(Position {
-                x: pos.x,
-                y: pos.y - 1,
-            });
-        }
-        if pos.y < self.height - 1 {
-            neighbours.push(Position {
-                x: pos.x,
-                y: pos.y + 1,
-            });
+    /// Find every route from a trailhead which climbs one step at a time
+    /// for `trail_length` cells, and add them to `paths`. Routes are counted
+    /// first, and maps with more than `MAX_PATHS` are an error rather than
+    /// running out of memory.
+    pub fn find_all_paths(&mut self, trail_length: usize) -> Result<(), Error> {
+        let paths = self.paths_iter(trail_length);
+        if paths.size_hint().1.is_none_or(|count| count > MAX_PATHS) {
+            return Err(Error::TooManyPaths { max: MAX_PATHS });
         }
+        let paths: Vec<Vec<Position>> = paths.collect();
+        self.paths.extend(paths);
+        Ok(())
+    }
+
+    /// Lazily walk the same routes as `find_all_paths`, in the same order,
+    /// without holding more than the current route in memory. Route counts
+    /// are worked out first so the walk never enters a cell which can't
+    /// reach a summit.
+    pub fn paths_iter(&self, trail_length: usize) -> Paths<'_> {
+        let (end, trailheads, routes) = match summit_height(trail_length) {
+            Some(end) => (end, self.trailheads.iter(), self.routes(end)),
+            None => (0, [].iter(), vec![]),
+        };
+        let remaining = trailheads
+            .clone()
+            .try_fold(0u64, |total, pos| {
+                total.checked_add(routes[self.coord(pos)])
+            })
+            .filter(|total| *total < u64::MAX);
 
-        if pos.x < self.width - 1 {
-            neighbours.push(Position {
-                x: pos.x + 1,
-                y: pos.y,
-            });
+        Paths {
+            map: self,
+            end,
+            routes,
+            trailheads,
+            route: vec![],
+            stack: vec![],
+            remaining,
         }
-        if pos.x > 0 {
-            neighbours.push(Position {
-                x: pos.x - 1,
-                y: pos.y,
-            });
+    }
+
+    /// Steps up from `index` which lead to at least one summit
+    fn climb(&self, index: usize, routes: &[u64]) -> std::vec::IntoIter<usize> {
+        self.uphill(index)
+            .filter(|next| routes[*next] > 0)
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// Cells no higher than `end`, highest first, so every cell comes after
+    /// the cells one step above it
+    fn downhill(&self, end: u8) -> Vec<usize> {
+        let mut cells: Vec<usize> = (0..self.map.len())
+            .filter(|index| self.map[*index] <= end && self.allows(&self.position(*index)))
+            .collect();
+        cells.sort_by_key(|index| Reverse(self.map[*index]));
+        cells
+    }
+
+    fn position(&self, index: usize) -> Position {
+        Position {
+            x: index % self.width,
+            y: index / self.width,
         }
-        neighbours
     }
 
-    // nicked from https://www.technical-recipes.com/2011/a-recursive-algorithm-to-find-all-paths-between-two-given-nodes/
-    // depth-first but backtracks to find all routes through a trail
-    fn get_paths(&mut self, visited: &mut VecDeque<Position>, end: u8) {
-        // get last element
-        let current = visited.back().expect("Empty");
-        let val = self.map[self.coord(current)];
+    /// Cells one step higher than `index`
+    fn uphill(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+        let val = self.map[index];
+        self.neighbours(&self.position(index))
+            .into_iter()
+            .map(|pos| self.coord(&pos))
+            .filter(move |next| self.map[*next] == val + 1)
+    }
 
-        let neighbours = self.neighbours(current);
+    /// Number of distinct summits each trailhead can reach, in `trailheads`
+    /// order. Worked out from the summits down so each cell is only visited
+    /// once rather than once per route through it.
+    pub fn scores(&self, trail_length: usize) -> Vec<usize> {
+        let Some(end) = summit_height(trail_length) else {
+            return vec![0; self.trailheads.len()];
+        };
 
-        // check for paths
-        for pos in &neighbours {
-            if visited.contains(pos) {
+        let mut summits: Vec<Vec<usize>> = vec![vec![]; self.map.len()];
+        for index in self.downhill(end) {
+            if self.map[index] == end {
+                summits[index] = vec![index];
                 continue;
             }
+            let mut reachable: Vec<usize> = self
+                .uphill(index)
+                .flat_map(|next| summits[next].iter().copied())
+                .collect();
+            reachable.sort();
+            reachable.dedup();
+            summits[index] = reachable;
+        }
 
-            let neighbour_val = self.map[self.coord(pos)];
-            if neighbour_val == val + 1 && neighbour_val == end {
-                visited.push_back(*pos);
-                let len = visited.len();
-                let hops = len - 1;
-
-                visited.make_contiguous();
-                let path = visited.as_slices().0;
-                self.paths.push(path.to_vec());
+        self.trailheads
+            .iter()
+            .map(|pos| summits[self.coord(pos)].len())
+            .collect()
+    }
 
-                visited.remove(hops);
-                break;
-            }
+    /// Number of routes from each cell up to a summit at height `end`.
+    /// Counts saturate rather than overflow on huge maps.
+    fn routes(&self, end: u8) -> Vec<u64> {
+        let mut routes = vec![0u64; self.map.len()];
+        for index in self.downhill(end) {
+            routes[index] = match self.map[index] == end {
+                true => 1,
+                false => self
+                    .uphill(index)
+                    .fold(0u64, |total, next| total.saturating_add(routes[next])),
+            };
         }
+        routes
+    }
+
+    /// Number of distinct routes from each trailhead to any summit, in
+    /// `trailheads` order
+    pub fn ratings(&self, trail_length: usize) -> Vec<u64> {
+        let Some(end) = summit_height(trail_length) else {
+            return vec![0; self.trailheads.len()];
+        };
+
+        let routes = self.routes(end);
+        self.trailheads
+            .iter()
+            .map(|pos| routes[self.coord(pos)])
+            .collect()
+    }
+}
 
-        // recurse
-        for pos in neighbours {
-            let neighbour_val = self.map[self.coord(&pos)];
-            if visited.contains(&pos) || neighbour_val == end {
+/// Iterator over every route up a map, from `Map::paths_iter`. Heights
+/// strictly increase along a route, so it can't revisit a cell and there's
+/// no need to track visited ones.
+#[derive(Debug)]
+pub struct Paths<'a> {
+    map: &'a Map,
+    end: u8,
+    /// Routes from each cell up to a summit
+    routes: Vec<u64>,
+    trailheads: std::slice::Iter<'a, Position>,
+    /// Cells on the route so far, and the steps up still to try from each
+    route: Vec<usize>,
+    stack: Vec<std::vec::IntoIter<usize>>,
+    /// Routes not yet walked, or `None` when there are too many to count
+    remaining: Option<u64>,
+}
+
+impl Iterator for Paths<'_> {
+    type Item = Vec<Position>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.nth(0)
+    }
+
+    /// Cells with fewer routes than are left to skip are passed over whole,
+    /// so skipping doesn't walk every route
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        let map = self.map;
+        let mut skip = n as u64;
+        loop {
+            let Some(steps) = self.stack.last_mut() else {
+                let start = map.coord(self.trailheads.next()?);
+                if map.map[start] == self.end || self.routes[start] == 0 {
+                    continue;
+                }
+                if self.routes[start] <= skip {
+                    self.skip(start, &mut skip);
+                    continue;
+                }
+                self.route = vec![start];
+                self.stack = vec![map.climb(start, &self.routes)];
                 continue;
-            }
-            if neighbour_val == val + 1 {
-                visited.push_back(pos);
-                self.get_paths(visited, end);
+            };
 
-                let len = visited.len();
-                if len > 0 {
-                    visited.remove(len - 1);
+            match steps.next() {
+                Some(next) if self.routes[next] <= skip => self.skip(next, &mut skip),
+                Some(next) if map.map[next] == self.end => {
+                    self.remaining = self.remaining.map(|remaining| remaining.saturating_sub(1));
+                    let path = self.route.iter().chain([&next]);
+                    return Some(path.map(|index| map.position(*index)).collect());
+                }
+                Some(next) => {
+                    self.route.push(next);
+                    self.stack.push(map.climb(next, &self.routes));
+                }
+                None => {
+                    self.stack.pop();
+                    self.route.pop();
                 }
             }
         }
     }
 
-    pub fn find_all_paths(&mut self) {
-        let mut visited = VecDeque::new();
-        for start in self.trailheads.clone()[..].iter() {
-            visited.push_back(*start);
-            self.get_paths(&mut visited, 9);
-            // reset for each starting point
-            visited.truncate(0);
-        }
+    /// The upper bound is exact when the routes could be counted. The lower
+    /// bound is 0, so collecting doesn't allocate room for every route up
+    /// front.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self
+            .remaining
+            .and_then(|remaining| usize::try_from(remaining).ok());
+        (0, remaining)
     }
 }
 
+impl Paths<'_> {
+    /// Pass over every route through `index`
+    fn skip(&mut self, index: usize, skip: &mut u64) {
+        *skip -= self.routes[index];
+        self.remaining = self
+            .remaining
+            .map(|remaining| remaining.saturating_sub(self.routes[index]));
+    }
+}
+
+/// Height of the last cell of a trail, if it fits in a cell
+fn summit_height(trail_length: usize) -> Option<u8> {
+    trail_length
+        .checked_sub(1)
+        .and_then(|end| u8::try_from(end).ok())
+}
+
 #[cfg(test)]
 mod test {
-    use crate::map::{Map, Position};
+    use std::collections::HashSet;
+
+    use crate::{
+        alphabet::Alphabet,
+        error::{Diagnostic, DiagnosticKind, Error},
+        map::{MAX_PATHS, Map, Position},
+        topology::Neighbourhood,
+    };
     #[test]
     fn it_should_parse_text_input() {
         let input = "0123
           1234
           8765
           9876";
-        let map = Map::parse(&input);
+        let map = Map::parse(input).expect("Failed to parse");
 
         assert_eq!(
             map,
@@ -163,6 +439,9 @@ mod test {
                 width: 4,
                 height: 4,
                 paths: vec![],
+                neighbourhood: Neighbourhood::VonNeumann,
+                wrap: false,
+                mask: None,
                 trailheads: vec![Position { x: 0, y: 0 }],
                 map: vec![0, 1, 2, 3, 1, 2, 3, 4, 8, 7, 6, 5, 9, 8, 7, 6],
             }
@@ -175,7 +454,7 @@ mod test {
           1204
           8760
           9076";
-        let map = Map::parse(&input);
+        let map = Map::parse(input).expect("Failed to parse");
 
         assert_eq!(
             map,
@@ -183,6 +462,9 @@ mod test {
                 width: 4,
                 height: 4,
                 paths: vec![],
+                neighbourhood: Neighbourhood::VonNeumann,
+                wrap: false,
+                mask: None,
                 trailheads: vec![
                     Position { x: 0, y: 0 },
                     Position { x: 2, y: 1 },
@@ -197,7 +479,7 @@ mod test {
           1214
           8761
           9176";
-        let map = Map::parse(&input);
+        let map = Map::parse(input).expect("Failed to parse");
 
         assert_eq!(
             map,
@@ -205,6 +487,9 @@ mod test {
                 width: 4,
                 height: 4,
                 paths: vec![],
+                neighbourhood: Neighbourhood::VonNeumann,
+                wrap: false,
+                mask: None,
                 trailheads: vec![],
                 map: vec![1, 1, 2, 3, 1, 2, 1, 4, 8, 7, 6, 1, 9, 1, 7, 6],
             }
@@ -217,7 +502,7 @@ mod test {
           1234
           8765
           9876";
-        let map = Map::parse(&input);
+        let map = Map::parse(input).expect("Failed to parse");
         assert_eq!(map.coord(&Position { x: 0, y: 0 }), 0);
         assert_eq!(map.coord(&Position { x: 0, y: 1 }), 4);
         assert_eq!(map.coord(&Position { x: 3, y: 3 }), 15);
@@ -230,7 +515,7 @@ mod test {
           1234
           8765
           9876";
-        let map = Map::parse(&input);
+        let map = Map::parse(input).expect("Failed to parse");
 
         assert_eq!(
             map.neighbours(&Position { x: 1, y: 1 }),
@@ -266,8 +551,8 @@ mod test {
           320
           013
           104";
-        let mut map = Map::parse(&input);
-        map.find_all_paths();
+        let mut map = Map::parse(input).expect("Failed to parse");
+        map.find_all_paths(10).expect("Too many paths");
         let expected: Vec<Vec<Position>> = vec![
             vec![
                 Position { x: 0, y: 6 },
@@ -320,4 +605,228 @@ mod test {
         ];
         assert_eq!(map.paths, expected);
     }
+
+    #[test]
+    fn it_should_score_and_rate_trailheads() {
+        let input = "89010123
+78121874
+87430965
+96549874
+45678903
+32019012
+01329801
+10456732";
+        let mut map = Map::parse(input).expect("Failed to parse");
+
+        assert_eq!(map.scores(10), vec![5, 6, 5, 3, 1, 3, 5, 3, 5]);
+        assert_eq!(map.ratings(10), vec![20, 24, 10, 4, 1, 4, 5, 8, 5]);
+
+        // the enumerated paths agree with the counts
+        map.find_all_paths(10).expect("Too many paths");
+        assert_eq!(map.paths.len() as u64, map.ratings(10).iter().sum::<u64>());
+        let pairs: HashSet<_> = map
+            .paths
+            .iter()
+            .map(|path| (path[0], path[path.len() - 1]))
+            .collect();
+        assert_eq!(pairs.len(), map.scores(10).iter().sum::<usize>());
+    }
+
+    #[test]
+    fn it_should_find_every_summit_next_to_a_cell() {
+        let mut map = Map::parse("012\n925").expect("Failed to parse");
+        map.find_all_paths(3).expect("Too many paths");
+
+        let start = Position { x: 0, y: 0 };
+        let middle = Position { x: 1, y: 0 };
+        assert_eq!(map.paths.len(), 2);
+        assert!(
+            map.paths
+                .contains(&vec![start, middle, Position { x: 2, y: 0 }])
+        );
+        assert!(
+            map.paths
+                .contains(&vec![start, middle, Position { x: 1, y: 1 }])
+        );
+    }
+
+    #[test]
+    fn it_should_stream_paths() {
+        let input = "89010123
+78121874
+87430965
+96549874
+45678903
+32019012
+01329801
+10456732";
+        let mut map = Map::parse(input).expect("Failed to parse");
+
+        let mut paths = map.paths_iter(10);
+        assert_eq!(paths.size_hint(), (0, Some(81)));
+        let first: Vec<_> = paths.by_ref().take(3).collect();
+        assert_eq!(paths.size_hint(), (0, Some(78)));
+
+        map.find_all_paths(10).expect("Too many paths");
+        assert_eq!(first, map.paths[..3]);
+        assert_eq!(map.paths_iter(10).collect::<Vec<_>>(), map.paths);
+        assert_eq!(map.paths_iter(0).next(), None);
+
+        // skipping passes over whole cells but lands on the same routes
+        let mut paths = map.paths_iter(10);
+        assert_eq!(paths.nth(40).as_ref(), map.paths.get(40));
+        assert_eq!(paths.nth(20).as_ref(), map.paths.get(61));
+        assert_eq!(paths.size_hint(), (0, Some(19)));
+        assert_eq!(paths.nth(19), None);
+    }
+
+    #[test]
+    fn it_should_refuse_too_many_paths() {
+        // 2^23 routes from the top left corner to the diagonal
+        let digits = "0123456789abcdefghijklmnopqrstuvwxyz";
+        let input: Vec<String> = (0..24)
+            .map(|y| (0..24).map(|x| &digits[(x + y).min(23)..][..1]).collect())
+            .collect();
+        let mut map =
+            Map::parse_with(&input.join("\n"), Alphabet::Base36).expect("Failed to parse");
+
+        assert_eq!(map.paths_iter(24).size_hint(), (0, Some(1 << 23)));
+        assert_eq!(
+            map.find_all_paths(24),
+            Err(Error::TooManyPaths { max: MAX_PATHS })
+        );
+        assert!(map.paths.is_empty());
+        let last = map.paths_iter(24).nth((1 << 23) - 1).expect("Missing path");
+        assert_eq!(last[23], Position { x: 23, y: 0 });
+    }
+
+    #[test]
+    fn it_should_reject_invalid_input() {
+        assert_eq!(Map::parse(""), Err(Error::EmptyInput));
+        assert_eq!(Map::parse(" \n\n"), Err(Error::EmptyInput));
+        assert_eq!(
+            Map::parse("012\n0a2"),
+            Err(Error::Parse(vec![Diagnostic {
+                line: 2,
+                column: 2,
+                kind: DiagnosticKind::InvalidChar('a')
+            }]))
+        );
+        assert_eq!(
+            Map::parse("012\n01\n012"),
+            Err(Error::Parse(vec![Diagnostic {
+                line: 2,
+                column: 3,
+                kind: DiagnosticKind::RaggedRow {
+                    expected: 3,
+                    found: 2
+                }
+            }]))
+        );
+    }
+
+    #[test]
+    fn it_should_report_every_problem() {
+        let input = "0123\r\n  1x34\n\n12345\n9876 \n";
+        assert_eq!(
+            Map::parse(input),
+            Err(Error::Parse(vec![
+                Diagnostic {
+                    line: 1,
+                    column: 5,
+                    kind: DiagnosticKind::CarriageReturn
+                },
+                Diagnostic {
+                    line: 2,
+                    column: 4,
+                    kind: DiagnosticKind::InvalidChar('x')
+                },
+                Diagnostic {
+                    line: 3,
+                    column: 1,
+                    kind: DiagnosticKind::BlankLine
+                },
+                Diagnostic {
+                    line: 4,
+                    column: 5,
+                    kind: DiagnosticKind::RaggedRow {
+                        expected: 4,
+                        found: 5
+                    }
+                },
+                Diagnostic {
+                    line: 5,
+                    column: 5,
+                    kind: DiagnosticKind::TrailingWhitespace
+                },
+            ]))
+        );
+    }
+
+    #[test]
+    fn it_should_format_diagnostics() {
+        let err = Map::parse("01\n0b\n1").expect_err("Should fail");
+        assert_eq!(
+            err.to_string(),
+            "Failed to parse map
+  line 2, column 2: invalid character 'b'
+  line 3, column 2: row has 1 columns but expected 2"
+        );
+    }
+
+    #[test]
+    fn it_should_parse_other_alphabets() {
+        let map = Map::parse_with("0az\nbc9", Alphabet::Base36).expect("Failed to parse");
+        assert_eq!(map.map, vec![0, 10, 35, 11, 12, 9]);
+        assert_eq!(map.trailheads, vec![Position { x: 0, y: 0 }]);
+
+        let map = Map::parse_with(" 0 12\n30  0", Alphabet::Separated).expect("Failed to parse");
+        assert_eq!((map.width, map.height), (2, 2));
+        assert_eq!(map.map, vec![0, 12, 30, 0]);
+
+        assert_eq!(
+            Map::parse_with("0 1 2\n5 x 300\n1 2", Alphabet::Separated),
+            Err(Error::Parse(vec![
+                Diagnostic {
+                    line: 2,
+                    column: 3,
+                    kind: DiagnosticKind::InvalidChar('x')
+                },
+                Diagnostic {
+                    line: 2,
+                    column: 5,
+                    kind: DiagnosticKind::InvalidValue(String::from("300"))
+                },
+                Diagnostic {
+                    line: 3,
+                    column: 4,
+                    kind: DiagnosticKind::RaggedRow {
+                        expected: 3,
+                        found: 2
+                    }
+                },
+            ]))
+        );
+    }
+
+    #[test]
+    fn it_should_skip_masked_cells() {
+        let mut map = Map::parse("012.\n..3.").expect("Failed to parse");
+        assert_eq!(map.trailheads, vec![Position { x: 0, y: 0 }]);
+        assert_eq!(
+            map.neighbours(&Position { x: 2, y: 0 }),
+            vec![Position { x: 2, y: 1 }, Position { x: 1, y: 0 }]
+        );
+
+        map.find_all_paths(4).expect("Too many paths");
+        assert_eq!(
+            map.paths,
+            vec![vec![
+                Position { x: 0, y: 0 },
+                Position { x: 1, y: 0 },
+                Position { x: 2, y: 0 },
+                Position { x: 2, y: 1 },
+            ]]
+        );
+    }
 }
@@ -0,0 +1,235 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::{
+    error::{Diagnostic, DiagnosticKind, Error},
+    map::Position,
+};
+
+/// Outline of the area trails are drawn in, stretched to fill the canvas
+#[derive(Debug, PartialEq, Clone)]
+pub enum Shape {
+    /// Rows of `#` for usable cells and `.` for unusable ones
+    Grid {
+        width: usize,
+        height: usize,
+        cells: Vec<bool>,
+    },
+    Circle,
+    /// Circle with a hole in the middle, `inner` times its radius
+    Ring {
+        inner: f64,
+    },
+    /// Corners as fractions of the canvas, from 0 to 1
+    Polygon(Vec<(f64, f64)>),
+}
+
+impl Display for Shape {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Shape::Grid { width, height, .. } => write!(f, "grid:{width}x{height}"),
+            Shape::Circle => write!(f, "circle"),
+            Shape::Ring { inner } => write!(f, "ring:{inner}"),
+            Shape::Polygon(points) => {
+                let points: Vec<String> = points.iter().map(|(x, y)| format!("{x},{y}")).collect();
+                write!(f, "polygon:{}", points.join(" "))
+            }
+        }
+    }
+}
+
+impl FromStr for Shape {
+    type Err = String;
+
+    /// `circle`, `ring`, `ring:0.6` or `polygon:0.5,0 1,1 0,1`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, args) = s.split_once(':').unwrap_or((s, ""));
+        match name {
+            "circle" => Ok(Shape::Circle),
+            "ring" if args.is_empty() => Ok(Shape::Ring { inner: 0.5 }),
+            "ring" => match args.parse::<f64>() {
+                Ok(inner) if (0.0..1.0).contains(&inner) => Ok(Shape::Ring { inner }),
+                _ => Err(format!("Ring size {args:?} should be from 0 to 1")),
+            },
+            "polygon" => {
+                let points = args
+                    .split_whitespace()
+                    .map(|point| {
+                        let (x, y) = point.split_once(',')?;
+                        Some((x.parse().ok()?, y.parse().ok()?))
+                    })
+                    .collect::<Option<Vec<(f64, f64)>>>()
+                    .filter(|points| points.len() >= 3)
+                    .ok_or_else(|| format!("Polygon {args:?} needs at least 3 x,y points"))?;
+                Ok(Shape::Polygon(points))
+            }
+            _ => Err(format!(
+                "Unknown mask {s:?}, expected circle, ring or polygon"
+            )),
+        }
+    }
+}
+
+impl Shape {
+    /// Read rows of `#` and `.`, reporting anything else by line and column.
+    /// Blank lines and surrounding whitespace are skipped.
+    pub fn grid(input: &str) -> Result<Self, Error> {
+        let mut width = None;
+        let mut height = 0;
+        let mut cells = vec![];
+        let mut diagnostics = vec![];
+        for (index, row) in input.lines().enumerate() {
+            let line = index + 1;
+            let trimmed = row.trim();
+            if trimmed.is_empty() {
+                continue;
+            }
+            // columns count from the start of the untrimmed row
+            let indent = row.chars().count() - row.trim_start().chars().count();
+            let found = trimmed.chars().count();
+            let expected = *width.get_or_insert(found);
+            height += 1;
+            if found != expected {
+                diagnostics.push(Diagnostic {
+                    line,
+                    column: indent + expected.min(found) + 1,
+                    kind: DiagnosticKind::RaggedRow { expected, found },
+                });
+            }
+            for (column, c) in trimmed.chars().enumerate() {
+                match c {
+                    '#' => cells.push(true),
+                    '.' => cells.push(false),
+                    _ => diagnostics.push(Diagnostic {
+                        line,
+                        column: indent + column + 1,
+                        kind: DiagnosticKind::InvalidChar(c),
+                    }),
+                }
+            }
+        }
+
+        let Some(width) = width else {
+            return Err(Error::EmptyInput);
+        };
+        if !diagnostics.is_empty() {
+            return Err(Error::Parse(diagnostics));
+        }
+        Ok(Shape::Grid {
+            width,
+            height,
+            cells,
+        })
+    }
+
+    /// Whether a point, as fractions of the canvas, is inside the shape
+    fn contains(&self, x: f64, y: f64) -> bool {
+        let from_centre = ((x - 0.5).powi(2) + (y - 0.5).powi(2)).sqrt();
+        match self {
+            Shape::Grid {
+                width,
+                height,
+                cells,
+            } => {
+                let column = ((x * *width as f64) as usize).min(width - 1);
+                let row = ((y * *height as f64) as usize).min(height - 1);
+                cells[row * width + column]
+            }
+            Shape::Circle => from_centre <= 0.5,
+            Shape::Ring { inner } => from_centre <= 0.5 && from_centre >= 0.5 * inner,
+            Shape::Polygon(points) => {
+                // even-odd rule: count edges crossed by a line to the right
+                let mut inside = false;
+                for (index, a) in points.iter().enumerate() {
+                    let b = points[(index + 1) % points.len()];
+                    if (a.1 > y) != (b.1 > y) && x < a.0 + (y - a.1) * (b.0 - a.0) / (b.1 - a.1) {
+                        inside = !inside;
+                    }
+                }
+                inside
+            }
+        }
+    }
+
+    /// Usable cells of a `width` by `height` canvas, tested at their centres
+    pub fn mask(&self, width: usize, height: usize) -> Mask {
+        let cells = (0..width * height)
+            .map(|index| {
+                let x = (index % width) as f64 + 0.5;
+                let y = (index / width) as f64 + 0.5;
+                self.contains(x / width as f64, y / height as f64)
+            })
+            .collect();
+        Mask { width, cells }
+    }
+}
+
+/// Which cells of a map trails can use
+#[derive(Debug, PartialEq, Clone)]
+pub struct Mask {
+    pub width: usize,
+    pub cells: Vec<bool>,
+}
+
+impl Mask {
+    pub fn allows(&self, pos: &Position) -> bool {
+        if pos.x >= self.width {
+            return false;
+        }
+        self.cells
+            .get(pos.y * self.width + pos.x)
+            .copied()
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        error::{Diagnostic, DiagnosticKind, Error},
+        map::Position,
+        mask::Shape,
+    };
+
+    #[test]
+    fn it_should_parse_grid_masks() {
+        let shape = Shape::grid(".#.\n###\n").expect("Failed to parse");
+        let mask = shape.mask(6, 4);
+        assert!(!mask.allows(&Position { x: 0, y: 0 }));
+        assert!(mask.allows(&Position { x: 2, y: 0 }));
+        assert!(mask.allows(&Position { x: 0, y: 3 }));
+
+        assert_eq!(
+            Shape::grid("#.\n#x"),
+            Err(Error::Parse(vec![Diagnostic {
+                line: 2,
+                column: 2,
+                kind: DiagnosticKind::InvalidChar('x')
+            }]))
+        );
+        // blank lines and indentation still count towards the position
+        assert_eq!(
+            Shape::grid("##\n\n  #?\n"),
+            Err(Error::Parse(vec![Diagnostic {
+                line: 3,
+                column: 4,
+                kind: DiagnosticKind::InvalidChar('?')
+            }]))
+        );
+        assert!(!mask.allows(&Position { x: 6, y: 0 }));
+    }
+
+    #[test]
+    fn it_should_mask_shapes() {
+        let count = |shape: &Shape| shape.mask(20, 20).cells.iter().filter(|c| **c).count();
+
+        let circle = count(&Shape::Circle);
+        // close to pi r squared
+        assert!((300..330).contains(&circle));
+        assert!(count(&"ring:0.5".parse().expect("Invalid")) < circle);
+        let triangle = count(&"polygon:0.5,0 1,1 0,1".parse().expect("Invalid"));
+        assert!((180..220).contains(&triangle));
+
+        assert!("ring:2".parse::<Shape>().is_err());
+        assert!("polygon:0,0 1,1".parse::<Shape>().is_err());
+    }
+}
@@ -0,0 +1,395 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::{
+    map::Position,
+    topology::{HEX_ROW_HEIGHT, Neighbourhood},
+};
+
+/// Where the pen starts before drawing anything
+const ORIGIN: Position = Position { x: 0, y: 0 };
+
+/// Stop improving after this many passes over every pair of trails
+const MAX_PASSES: usize = 20;
+
+/// Longest run of trails 2-opt will try reversing
+const WINDOW: usize = 64;
+
+/// Order trails are drawn in
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum PathOrdering {
+    /// Trailhead scan order, as the trails were found
+    #[default]
+    Scan,
+    /// Nearest neighbour then 2-opt to cut pen up travel, keeping each
+    /// trail's direction
+    Travel,
+    /// As `Travel`, but trails may also be drawn from the summit back to
+    /// the trailhead
+    Reversible,
+}
+
+impl Display for PathOrdering {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PathOrdering::Scan => write!(f, "scan"),
+            PathOrdering::Travel => write!(f, "travel"),
+            PathOrdering::Reversible => write!(f, "reversible"),
+        }
+    }
+}
+
+impl FromStr for PathOrdering {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "scan" => Ok(PathOrdering::Scan),
+            "travel" => Ok(PathOrdering::Travel),
+            "reversible" => Ok(PathOrdering::Reversible),
+            _ => Err(format!(
+                "Unknown path ordering {s:?}, expected scan, travel or reversible"
+            )),
+        }
+    }
+}
+
+/// Path index into `Map::paths`, and whether it's drawn end first
+pub type Step = (usize, bool);
+
+/// Drawing order for `Map::paths` with the pen up travel, in tiles, of
+/// scan order and of the chosen order. Travel is measured between points
+/// as they're laid out for the neighbourhood, so hex rows are shifted and
+/// squashed as they are when drawn.
+#[derive(Debug, PartialEq, Clone)]
+pub struct PathOrder {
+    pub order: Vec<Step>,
+    pub before: f64,
+    pub after: f64,
+}
+
+impl PathOrder {
+    pub fn new(
+        paths: &[Vec<Position>],
+        ordering: PathOrdering,
+        neighbourhood: Neighbourhood,
+    ) -> Self {
+        let scan: Vec<Step> = (0..paths.len()).map(|index| (index, false)).collect();
+        let before = travel(paths, &scan, neighbourhood);
+
+        let order = match ordering {
+            PathOrdering::Scan => scan,
+            PathOrdering::Travel => {
+                let order = nearest_neighbour(paths, false, neighbourhood);
+                two_opt(paths, order, false, neighbourhood)
+            }
+            PathOrdering::Reversible => {
+                let order = nearest_neighbour(paths, true, neighbourhood);
+                two_opt(paths, order, true, neighbourhood)
+            }
+        };
+        let after = travel(paths, &order, neighbourhood);
+
+        PathOrder {
+            order,
+            before,
+            after,
+        }
+    }
+}
+
+/// Where a grid point is drawn in tiles, laid out as `Svg` does
+fn layout(pos: Position, neighbourhood: Neighbourhood) -> (f64, f64) {
+    let (x, y) = (pos.x as f64, pos.y as f64);
+    match neighbourhood {
+        Neighbourhood::Hex => (x + y / 2.0, y * HEX_ROW_HEIGHT),
+        _ => (x, y),
+    }
+}
+
+fn distance(a: Position, b: Position, neighbourhood: Neighbourhood) -> f64 {
+    let (ax, ay) = layout(a, neighbourhood);
+    let (bx, by) = layout(b, neighbourhood);
+    ((ax - bx).powi(2) + (ay - by).powi(2)).sqrt()
+}
+
+/// First and last point of a trail in drawing direction
+fn ends(paths: &[Vec<Position>], (index, reversed): Step) -> (Position, Position) {
+    let path = &paths[index];
+    let first = path.first().copied().unwrap_or(ORIGIN);
+    let last = path.last().copied().unwrap_or(ORIGIN);
+    if reversed {
+        (last, first)
+    } else {
+        (first, last)
+    }
+}
+
+/// Pen up distance from the origin through every trail in `order`
+pub fn travel(paths: &[Vec<Position>], order: &[Step], neighbourhood: Neighbourhood) -> f64 {
+    let mut current = ORIGIN;
+    let mut total = 0.0;
+    for step in order {
+        let (head, tail) = ends(paths, *step);
+        total += distance(current, head, neighbourhood);
+        current = tail;
+    }
+    total
+}
+
+/// Undrawn trails bucketed by the cell they'd start drawing from, so the
+/// nearest can be found by searching outwards from the pen
+struct Heads {
+    width: usize,
+    height: usize,
+    cells: Vec<Vec<Step>>,
+}
+
+impl Heads {
+    fn new(paths: &[Vec<Position>], reversible: bool) -> Self {
+        let points = paths.iter().flatten();
+        let width = points.clone().map(|pos| pos.x + 1).max().unwrap_or(1);
+        let height = points.map(|pos| pos.y + 1).max().unwrap_or(1);
+
+        let mut cells = vec![vec![]; width * height];
+        for index in 0..paths.len() {
+            let directions: &[bool] = if reversible { &[false, true] } else { &[false] };
+            for reversed in directions {
+                let head = ends(paths, (index, *reversed)).0;
+                cells[head.y * width + head.x].push((index, *reversed));
+            }
+        }
+
+        Heads {
+            width,
+            height,
+            cells,
+        }
+    }
+
+    /// Closest undrawn trail to `pos`, preferring lower indexes on a tie
+    fn nearest(
+        &mut self,
+        paths: &[Vec<Position>],
+        pos: Position,
+        drawn: &[bool],
+        neighbourhood: Neighbourhood,
+    ) -> Option<Step> {
+        let mut best: Option<(f64, Step)> = None;
+        // closest a cell can be per ring out. Hex rows are squashed, so a
+        // ring comes in to a row height per step
+        let ring_gap = match neighbourhood {
+            Neighbourhood::Hex => HEX_ROW_HEIGHT,
+            _ => 1.0,
+        };
+
+        for radius in 0..self.width.max(self.height) {
+            // every cell in this ring is at least `radius * ring_gap` away
+            if best.is_some_and(|(dist, _)| dist < radius as f64 * ring_gap) {
+                break;
+            }
+
+            let (x, y) = (pos.x as isize, pos.y as isize);
+            let r = radius as isize;
+            for cy in (y - r).max(0)..=(y + r).min(self.height as isize - 1) {
+                let edge = cy == y - r || cy == y + r;
+                let step = if edge || r == 0 { 1 } else { 2 * r as usize };
+                for cx in ((x - r)..=(x + r)).step_by(step) {
+                    if cx < 0 || cx >= self.width as isize {
+                        continue;
+                    }
+                    let cell = &mut self.cells[cy as usize * self.width + cx as usize];
+                    cell.retain(|step| !drawn[step.0]);
+                    for step in cell.iter() {
+                        let dist = distance(pos, ends(paths, *step).0, neighbourhood);
+                        if best.is_none_or(|best| (dist, *step) < best) {
+                            best = Some((dist, *step));
+                        }
+                    }
+                }
+            }
+        }
+
+        best.map(|(_, step)| step)
+    }
+}
+
+/// Greedily draw whichever trail starts closest to where the pen is
+fn nearest_neighbour(
+    paths: &[Vec<Position>],
+    reversible: bool,
+    neighbourhood: Neighbourhood,
+) -> Vec<Step> {
+    let mut heads = Heads::new(paths, reversible);
+    let mut drawn = vec![false; paths.len()];
+    let mut order = Vec::with_capacity(paths.len());
+    let mut current = ORIGIN;
+
+    while let Some(step) = heads.nearest(paths, current, &drawn, neighbourhood) {
+        drawn[step.0] = true;
+        current = ends(paths, step).1;
+        order.push(step);
+    }
+
+    order
+}
+
+/// Reverse runs of trails while it shortens the total travel. Reversing a
+/// run also flips each trail in it if `reversible`, otherwise trails keep
+/// their direction and the gaps inside the run change. Only runs up to
+/// `WINDOW` long are tried since nearest neighbour has already put nearby
+/// trails close together.
+fn two_opt(
+    paths: &[Vec<Position>],
+    mut order: Vec<Step>,
+    reversible: bool,
+    neighbourhood: Neighbourhood,
+) -> Vec<Step> {
+    for _ in 0..MAX_PASSES {
+        let mut improved = false;
+
+        for i in 0..order.len() {
+            let prev = match i {
+                0 => ORIGIN,
+                _ => ends(paths, order[i - 1]).1,
+            };
+            let (first_head, first_tail) = ends(paths, order[i]);
+            // travel between the trails in the run, as is and reversed
+            let mut inner_before = 0.0;
+            let mut inner_after = 0.0;
+
+            for j in i..order.len().min(i + WINDOW) {
+                let (last_head, last_tail) = ends(paths, order[j]);
+                if j > i {
+                    let (before_head, before_tail) = ends(paths, order[j - 1]);
+                    inner_before += distance(before_tail, last_head, neighbourhood);
+                    inner_after += distance(last_tail, before_head, neighbourhood);
+                } else if !reversible {
+                    continue;
+                }
+
+                let next = order.get(j + 1).map(|step| ends(paths, *step).0);
+                let to_next =
+                    |from: Position| next.map_or(0.0, |next| distance(from, next, neighbourhood));
+                let before = distance(prev, first_head, neighbourhood) + to_next(last_tail);
+                let after = if reversible {
+                    // flipped trails keep the same gaps between them
+                    distance(prev, last_tail, neighbourhood) + to_next(first_head)
+                } else {
+                    distance(prev, last_head, neighbourhood) + to_next(first_tail) + inner_after
+                        - inner_before
+                };
+
+                if before - after > 1e-9 {
+                    order[i..=j].reverse();
+                    if reversible {
+                        for step in &mut order[i..=j] {
+                            step.1 = !step.1;
+                        }
+                    }
+                    improved = true;
+                    // the run totals no longer match the order
+                    break;
+                }
+            }
+        }
+
+        if !improved {
+            break;
+        }
+    }
+
+    order
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        map::Position,
+        order::{PathOrder, PathOrdering, travel},
+        topology::Neighbourhood,
+    };
+
+    fn line(from: (usize, usize), to: (usize, usize)) -> Vec<Position> {
+        vec![
+            Position {
+                x: from.0,
+                y: from.1,
+            },
+            Position { x: to.0, y: to.1 },
+        ]
+    }
+
+    fn paths() -> Vec<Vec<Position>> {
+        vec![
+            line((20, 0), (20, 5)),
+            line((0, 1), (0, 5)),
+            line((10, 0), (10, 5)),
+            line((21, 6), (21, 10)),
+        ]
+    }
+
+    #[test]
+    fn it_should_keep_scan_order() {
+        let order = PathOrder::new(&paths(), PathOrdering::Scan, Neighbourhood::VonNeumann);
+        assert_eq!(
+            order.order,
+            vec![(0, false), (1, false), (2, false), (3, false)]
+        );
+        assert_eq!(order.before, order.after);
+    }
+
+    #[test]
+    fn it_should_reduce_travel() {
+        let paths = paths();
+        let order = PathOrder::new(&paths, PathOrdering::Travel, Neighbourhood::VonNeumann);
+
+        assert!(order.order.iter().all(|(_, reversed)| !reversed));
+        assert_eq!(order.order[0], (1, false));
+        assert!(order.after < order.before);
+        assert_eq!(
+            order.after,
+            travel(&paths, &order.order, Neighbourhood::VonNeumann)
+        );
+    }
+
+    #[test]
+    fn it_should_reverse_paths() {
+        let paths = paths();
+        let order = PathOrder::new(&paths, PathOrdering::Reversible, Neighbourhood::VonNeumann);
+
+        // down the first line, back up the middle one, then along the right
+        assert_eq!(
+            order.order,
+            vec![(1, false), (2, true), (0, false), (3, false)]
+        );
+        assert!(
+            order.after
+                < PathOrder::new(&paths, PathOrdering::Travel, Neighbourhood::VonNeumann).after
+        );
+
+        let mut indexes: Vec<usize> = order.order.iter().map(|(index, _)| *index).collect();
+        indexes.sort();
+        assert_eq!(indexes, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn it_should_measure_hex_travel_as_drawn() {
+        // one row down and half a tile left is a neighbouring hex
+        let paths = vec![line((0, 0), (1, 0)), line((1, 1), (2, 1))];
+        let order = [(0, false), (1, false)];
+
+        assert_eq!(travel(&paths, &order, Neighbourhood::VonNeumann), 1.0);
+        assert!((travel(&paths, &order, Neighbourhood::Hex) - 1.0).abs() < 1e-9);
+
+        // two rows down and a tile left is straight down when drawn
+        let paths = vec![line((0, 0), (1, 0)), line((0, 2), (1, 2))];
+        let hex = travel(&paths, &order, Neighbourhood::Hex);
+        assert!((hex - 3.0_f64.sqrt()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn it_should_parse_ordering() {
+        assert_eq!("travel".parse::<PathOrdering>(), Ok(PathOrdering::Travel));
+        assert!("random".parse::<PathOrdering>().is_err());
+    }
+}
@@ -0,0 +1,287 @@
+use std::{collections::HashMap, fmt::Display, str::FromStr};
+
+use rand::{Rng, rngs::SmallRng};
+use rand_seeder::Seeder;
+
+use crate::{map::Map, quadtree::Leaf};
+
+/// Built in palettes, selectable by name
+pub const PALETTES: [(&str, &[&str]); 5] = [
+    ("mono", &["black"]),
+    ("ocean", &["#03045e", "#0077b6", "#00b4d8", "#90e0ef"]),
+    (
+        "sunset",
+        &["#f94144", "#f3722c", "#f8961e", "#f9c74f", "#90be6d"],
+    ),
+    ("forest", &["#283618", "#606c38", "#bc6c25", "#dda15e"]),
+    (
+        "neon",
+        &["#ff00c1", "#9600ff", "#4900ff", "#00b8ff", "#00fff9"],
+    ),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    pub colours: Vec<String>,
+}
+
+impl Palette {
+    pub fn named(name: &str) -> Option<Self> {
+        PALETTES
+            .iter()
+            .find(|(palette_name, _)| *palette_name == name)
+            .map(|(_, colours)| Palette {
+                colours: colours.iter().map(|colour| colour.to_string()).collect(),
+            })
+    }
+
+    /// Colour for each of `map.paths`, see `assign_colours`
+    pub fn trail_colours(
+        &self,
+        map: &Map,
+        strategy: ColourStrategy,
+        root: Option<&Leaf>,
+        seed: &str,
+    ) -> Vec<&str> {
+        assign_colours(map, strategy, self.colours.len(), root, seed)
+            .into_iter()
+            .map(|index| self.colours[index].as_str())
+            .collect()
+    }
+}
+
+/// Colours end up inside SVG attributes, so only allow names and hex values
+fn is_valid_colour(colour: &str) -> bool {
+    !colour.is_empty()
+        && colour
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '#')
+}
+
+impl FromStr for Palette {
+    type Err = String;
+
+    /// Either a built in palette name or a comma separated list of colours
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(palette) = Palette::named(s) {
+            return Ok(palette);
+        }
+
+        let colours: Vec<String> = s.split(',').map(|c| c.trim().to_string()).collect();
+        if colours.len() < 2 || !colours.iter().all(|c| is_valid_colour(c)) {
+            let names: Vec<&str> = PALETTES.iter().map(|(name, _)| *name).collect();
+            return Err(format!(
+                "Unknown palette {s:?}, expected one of {} or a comma separated list of colours",
+                names.join(", ")
+            ));
+        }
+
+        Ok(Palette { colours })
+    }
+}
+
+/// How trails are matched to palette colours
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum ColourStrategy {
+    /// Every path from the same trailhead shares a colour
+    #[default]
+    Trailhead,
+    /// Cycle through the palette in path order
+    PathIndex,
+    /// Bucket by how far apart the start and end of a trail are
+    Length,
+    /// Every trail starting in the same quadtree leaf shares a colour
+    Leaf,
+    /// Pick at random, seeded so the same seed gives the same colours
+    Random,
+}
+
+impl Display for ColourStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColourStrategy::Trailhead => write!(f, "trailhead"),
+            ColourStrategy::PathIndex => write!(f, "index"),
+            ColourStrategy::Length => write!(f, "length"),
+            ColourStrategy::Leaf => write!(f, "leaf"),
+            ColourStrategy::Random => write!(f, "random"),
+        }
+    }
+}
+
+impl FromStr for ColourStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "trailhead" => Ok(ColourStrategy::Trailhead),
+            "index" => Ok(ColourStrategy::PathIndex),
+            "length" => Ok(ColourStrategy::Length),
+            "leaf" => Ok(ColourStrategy::Leaf),
+            "random" => Ok(ColourStrategy::Random),
+            _ => Err(format!(
+                "Unknown colour strategy {s:?}, expected trailhead, index, length, leaf or random"
+            )),
+        }
+    }
+}
+
+/// Pick a palette index for each of `map.paths`. Without a quadtree the
+/// leaf strategy falls back to colouring by trailhead.
+pub fn assign_colours(
+    map: &Map,
+    strategy: ColourStrategy,
+    palette_len: usize,
+    root: Option<&Leaf>,
+    seed: &str,
+) -> Vec<usize> {
+    if palette_len == 0 {
+        return vec![0; map.paths.len()];
+    }
+
+    let trailheads: HashMap<_, _> = map
+        .trailheads
+        .iter()
+        .enumerate()
+        .map(|(index, pos)| (*pos, index))
+        .collect();
+    let by_trailhead = |path: &Vec<_>| {
+        path.first()
+            .and_then(|start| trailheads.get(start))
+            .copied()
+            .unwrap_or(0)
+    };
+
+    match (strategy, root) {
+        (ColourStrategy::Trailhead, _) | (ColourStrategy::Leaf, None) => map
+            .paths
+            .iter()
+            .map(|path| by_trailhead(path) % palette_len)
+            .collect(),
+        (ColourStrategy::PathIndex, _) => (0..map.paths.len())
+            .map(|index| index % palette_len)
+            .collect(),
+        (ColourStrategy::Length, _) => {
+            let spans: Vec<usize> = map
+                .paths
+                .iter()
+                .map(|path| match (path.first(), path.last()) {
+                    (Some(start), Some(end)) => start.x.abs_diff(end.x) + start.y.abs_diff(end.y),
+                    _ => 0,
+                })
+                .collect();
+            let min = spans.iter().min().copied().unwrap_or(0);
+            let max = spans.iter().max().copied().unwrap_or(0);
+            spans
+                .iter()
+                .map(|span| (span - min) * palette_len / (max - min + 1))
+                .collect()
+        }
+        (ColourStrategy::Leaf, Some(root)) => {
+            let leaves = root.leaves();
+            map.paths
+                .iter()
+                .map(|path| {
+                    path.first()
+                        .and_then(|start| leaves.iter().position(|leaf| leaf.contains(start)))
+                        .unwrap_or_else(|| by_trailhead(path))
+                        % palette_len
+                })
+                .collect()
+        }
+        (ColourStrategy::Random, _) => {
+            let mut rng: SmallRng = Seeder::from(seed).into_rng();
+            map.paths
+                .iter()
+                .map(|_| rng.random_range(0..palette_len))
+                .collect()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        map::{Map, Position},
+        palette::{ColourStrategy, Palette, assign_colours},
+        quadtree::Leaf,
+    };
+
+    fn map() -> Map {
+        let input = "890
+          781
+          874
+          965
+          456
+          320
+          013
+          104";
+        let mut map = Map::parse(input).expect("Failed to parse");
+        map.find_all_paths(10).expect("Too many paths");
+        map
+    }
+
+    #[test]
+    fn it_should_parse_palettes() {
+        assert_eq!(
+            "mono".parse::<Palette>(),
+            Ok(Palette {
+                colours: vec![String::from("black")]
+            })
+        );
+        assert_eq!(
+            "red, #00ff00".parse::<Palette>(),
+            Ok(Palette {
+                colours: vec![String::from("red"), String::from("#00ff00")]
+            })
+        );
+        assert!("nope".parse::<Palette>().is_err());
+        assert!("red,\"/><script>".parse::<Palette>().is_err());
+    }
+
+    #[test]
+    fn it_should_assign_by_trailhead_and_index() {
+        let map = map();
+        assert_eq!(
+            assign_colours(&map, ColourStrategy::Trailhead, 3, None, "seed"),
+            vec![2, 2, 0, 0]
+        );
+        assert_eq!(
+            assign_colours(&map, ColourStrategy::PathIndex, 3, None, "seed"),
+            vec![0, 1, 2, 0]
+        );
+    }
+
+    #[test]
+    fn it_should_assign_by_length() {
+        let map = map();
+        // two trails end at (1, 0), two at (0, 3)
+        assert_eq!(
+            assign_colours(&map, ColourStrategy::Length, 2, None, "seed"),
+            vec![1, 0, 1, 0]
+        );
+    }
+
+    #[test]
+    fn it_should_assign_by_leaf() {
+        let mut map = map();
+        map.paths[1][0] = Position { x: 2, y: 2 };
+        let mut root = Leaf::new(0, 0, 3, 8, 1, 0);
+        root.children = vec![Leaf::new(0, 0, 3, 4, 1, 1), Leaf::new(0, 4, 3, 4, 1, 1)];
+
+        assert_eq!(
+            assign_colours(&map, ColourStrategy::Leaf, 4, Some(&root), "seed"),
+            vec![1, 0, 1, 1]
+        );
+    }
+
+    #[test]
+    fn it_should_assign_randomly_from_seed() {
+        let map = map();
+        let first = assign_colours(&map, ColourStrategy::Random, 5, None, "seed");
+        assert_eq!(
+            first,
+            assign_colours(&map, ColourStrategy::Random, 5, None, "seed")
+        );
+        assert!(first.iter().all(|index| *index < 5));
+    }
+}
@@ -0,0 +1,375 @@
+use std::{fmt::Display, str::FromStr};
+
+use rand::{Rng, rngs::SmallRng};
+
+use crate::{
+    map::Position,
+    quadtree::{Leaf, SplitRules},
+};
+
+/// Splits the canvas into the leaves trails start in
+pub trait Partitioner {
+    /// Tree of leaves covering a `width` by `height` canvas. Leaves are at
+    /// least `min_size` cells across where the canvas allows it.
+    fn partition(&self, width: usize, height: usize, min_size: usize, rng: &mut SmallRng) -> Leaf;
+}
+
+/// Quarters each leaf, as `Leaf::generate_with`
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Quadtree(pub SplitRules);
+
+impl Partitioner for Quadtree {
+    fn partition(&self, width: usize, height: usize, min_size: usize, rng: &mut SmallRng) -> Leaf {
+        let mut root = Leaf::new(0, 0, width, height, min_size, 0);
+        root.generate_with(&self.0, rng);
+        root
+    }
+}
+
+/// Halves of `leaf` either side of a cut `at` cells along x or y
+fn cut(leaf: &Leaf, vertical: bool, at: usize) -> Vec<Leaf> {
+    let depth = leaf.depth + 1;
+    match vertical {
+        true => vec![
+            Leaf::new(leaf.x, leaf.y, at, leaf.height, leaf.min_size, depth),
+            Leaf::new(
+                leaf.x + at,
+                leaf.y,
+                leaf.width - at,
+                leaf.height,
+                leaf.min_size,
+                depth,
+            ),
+        ],
+        false => vec![
+            Leaf::new(leaf.x, leaf.y, leaf.width, at, leaf.min_size, depth),
+            Leaf::new(
+                leaf.x,
+                leaf.y + at,
+                leaf.width,
+                leaf.height - at,
+                leaf.min_size,
+                depth,
+            ),
+        ],
+    }
+}
+
+/// Cuts each leaf in two at a random ratio, across its longer side or
+/// either way for squarish leaves. Splits follow the same rules as the
+/// quadtree.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Bsp(pub SplitRules);
+
+impl Bsp {
+    fn split(&self, leaf: &mut Leaf, rng: &mut SmallRng) {
+        if !self.0.should_split(leaf.depth, rng) {
+            return;
+        }
+        let fits = |size: usize| size >= leaf.min_size * 2;
+        let vertical = match (fits(leaf.width), fits(leaf.height)) {
+            (false, false) => return,
+            (true, false) => true,
+            (false, true) => false,
+            _ if leaf.width * 4 > leaf.height * 5 => true,
+            _ if leaf.height * 4 > leaf.width * 5 => false,
+            _ => rng.random_bool(0.5),
+        };
+        let size = if vertical { leaf.width } else { leaf.height };
+        let at = ((size as f64 * rng.random_range(0.3..0.7)).round() as usize)
+            .clamp(leaf.min_size, size - leaf.min_size);
+
+        leaf.children = cut(leaf, vertical, at);
+        leaf.children
+            .iter_mut()
+            .for_each(|child| self.split(child, rng));
+    }
+}
+
+impl Partitioner for Bsp {
+    fn partition(&self, width: usize, height: usize, min_size: usize, rng: &mut SmallRng) -> Leaf {
+        let mut root = Leaf::new(0, 0, width, height, min_size, 0);
+        self.split(&mut root, rng);
+        root
+    }
+}
+
+/// Scatters random points, then cuts at their median along x and y in turn
+/// until each leaf holds one point, so leaves are smaller where points
+/// happen to bunch up
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct KdTree;
+
+impl KdTree {
+    fn split(&self, leaf: &mut Leaf, mut points: Vec<Position>) {
+        if points.len() < 2 {
+            return;
+        }
+        let fits = |size: usize| size >= leaf.min_size * 2;
+        let vertical = match (fits(leaf.width), fits(leaf.height)) {
+            (false, false) => return,
+            (true, false) => true,
+            (false, true) => false,
+            _ => leaf.depth.is_multiple_of(2),
+        };
+
+        let axis = |pos: &Position| if vertical { pos.x } else { pos.y };
+        points.sort_by_key(axis);
+        let (start, size) = match vertical {
+            true => (leaf.x, leaf.width),
+            false => (leaf.y, leaf.height),
+        };
+        let median = axis(&points[points.len() / 2]);
+        let at = (median - start).clamp(leaf.min_size, size - leaf.min_size);
+
+        leaf.children = cut(leaf, vertical, at);
+        let (before, after) = points.into_iter().partition(|pos| axis(pos) < start + at);
+        self.split(&mut leaf.children[0], before);
+        self.split(&mut leaf.children[1], after);
+    }
+}
+
+/// Random points with about one for each `min_size * min_size * 4` cells,
+/// so leaves are a similar size to the quadtree's
+fn scatter(width: usize, height: usize, min_size: usize, rng: &mut SmallRng) -> Vec<Position> {
+    let count = (width * height / (min_size * min_size * 4)).max(1);
+    (0..count)
+        .map(|_| Position {
+            x: rng.random_range(0..width),
+            y: rng.random_range(0..height),
+        })
+        .collect()
+}
+
+impl Partitioner for KdTree {
+    fn partition(&self, width: usize, height: usize, min_size: usize, rng: &mut SmallRng) -> Leaf {
+        let mut root = Leaf::new(0, 0, width, height, min_size, 0);
+        self.split(&mut root, scatter(width, height, min_size, rng));
+        root
+    }
+}
+
+/// Sites sorted into squares about as wide as the gaps between them, so
+/// finding the closest only searches the squares around a cell
+struct Buckets {
+    size: usize,
+    columns: usize,
+    rows: usize,
+    /// Indices of the sites in each square, row by row
+    sites: Vec<Vec<usize>>,
+}
+
+impl Buckets {
+    fn new(sites: &[Position], width: usize, height: usize, size: usize) -> Self {
+        let size = size.max(1);
+        let (columns, rows) = (width.div_ceil(size), height.div_ceil(size));
+        let mut buckets = vec![vec![]; columns * rows];
+        for (index, site) in sites.iter().enumerate() {
+            buckets[(site.y / size) * columns + site.x / size].push(index);
+        }
+        Buckets {
+            size,
+            columns,
+            rows,
+            sites: buckets,
+        }
+    }
+
+    /// Index of the site closest to `cell`, the lowest index winning ties.
+    /// Squares are searched in rings until the closest found is nearer than
+    /// anything in the next ring could be.
+    fn nearest(&self, sites: &[Position], cell: &Position) -> Option<usize> {
+        let (column, row) = ((cell.x / self.size) as isize, (cell.y / self.size) as isize);
+        let mut nearest: Option<(usize, usize)> = None;
+        for ring in 0..=self.columns.max(self.rows) as isize {
+            // sites in this ring are at least this far away
+            let gap = (ring.max(1) - 1) as usize * self.size;
+            if nearest.is_some_and(|(distance, _)| distance < gap * gap) {
+                break;
+            }
+            for y in row - ring..=row + ring {
+                for x in column - ring..=column + ring {
+                    let outside =
+                        x < 0 || y < 0 || x >= self.columns as isize || y >= self.rows as isize;
+                    if outside || (x - column).abs().max((y - row).abs()) != ring {
+                        continue;
+                    }
+                    for site in &self.sites[y as usize * self.columns + x as usize] {
+                        let distance = sites[*site].x.abs_diff(cell.x).pow(2)
+                            + sites[*site].y.abs_diff(cell.y).pow(2);
+                        if nearest.is_none_or(|nearest| (distance, *site) < nearest) {
+                            nearest = Some((distance, *site));
+                        }
+                    }
+                }
+            }
+        }
+        nearest.map(|(_, site)| site)
+    }
+}
+
+/// Cells grouped by whichever random site is closest, for rounded,
+/// irregular leaves. Leaves are children of the root, each with a `region`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Voronoi;
+
+impl Partitioner for Voronoi {
+    fn partition(&self, width: usize, height: usize, min_size: usize, rng: &mut SmallRng) -> Leaf {
+        let mut sites = scatter(width, height, min_size, rng);
+        sites.sort();
+        sites.dedup();
+
+        // cells grouped by their closest site, earlier sites winning ties
+        let buckets = Buckets::new(&sites, width, height, min_size * 2);
+        let mut regions: Vec<Vec<usize>> = vec![vec![]; sites.len()];
+        for index in 0..width * height {
+            let cell = Position {
+                x: index % width,
+                y: index / width,
+            };
+            if let Some(nearest) = buckets.nearest(&sites, &cell) {
+                regions[nearest].push(index);
+            }
+        }
+
+        let mut root = Leaf::new(0, 0, width, height, min_size, 0);
+        for cells in regions {
+            let columns = cells.iter().map(|index| index % width);
+            let rows = cells.iter().map(|index| index / width);
+            // every site is closest to its own cell, so none are empty
+            let (Some(left), Some(right)) = (columns.clone().min(), columns.max()) else {
+                continue;
+            };
+            let (top, bottom) = (rows.clone().min().unwrap_or(0), rows.max().unwrap_or(0));
+
+            let mut leaf = Leaf::new(left, top, right - left + 1, bottom - top + 1, min_size, 1);
+            let mut region = vec![false; leaf.width * leaf.height];
+            for index in cells {
+                region[(index / width - top) * leaf.width + index % width - left] = true;
+            }
+            leaf.region = Some(region);
+            root.children.push(leaf);
+        }
+        root
+    }
+}
+
+/// Partitioner chosen in `GenerationConfig`
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Partition {
+    #[default]
+    Quadtree,
+    Bsp,
+    KdTree,
+    Voronoi,
+}
+
+impl Display for Partition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Partition::Quadtree => write!(f, "quadtree"),
+            Partition::Bsp => write!(f, "bsp"),
+            Partition::KdTree => write!(f, "kd"),
+            Partition::Voronoi => write!(f, "voronoi"),
+        }
+    }
+}
+
+impl FromStr for Partition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "quadtree" => Ok(Partition::Quadtree),
+            "bsp" => Ok(Partition::Bsp),
+            "kd" => Ok(Partition::KdTree),
+            "voronoi" => Ok(Partition::Voronoi),
+            _ => Err(format!(
+                "Unknown partition {s:?}, expected quadtree, bsp, kd or voronoi"
+            )),
+        }
+    }
+}
+
+impl Partition {
+    /// Quadtree and BSP leaves split following `rules`
+    pub fn partitioner(&self, rules: SplitRules) -> Box<dyn Partitioner> {
+        match self {
+            Partition::Quadtree => Box::new(Quadtree(rules)),
+            Partition::Bsp => Box::new(Bsp(rules)),
+            Partition::KdTree => Box::new(KdTree),
+            Partition::Voronoi => Box::new(Voronoi),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{SeedableRng, rngs::SmallRng};
+
+    use crate::{
+        map::Position,
+        partition::{Buckets, Partition, scatter},
+        placement::{PerLeaf, StartPlacement},
+        quadtree::SplitRules,
+    };
+
+    #[test]
+    fn it_should_cover_canvas_once() {
+        for partition in [
+            Partition::Quadtree,
+            Partition::Bsp,
+            Partition::KdTree,
+            Partition::Voronoi,
+        ] {
+            let mut rng = SmallRng::seed_from_u64(123);
+            let root = partition
+                .partitioner(SplitRules::default())
+                .partition(37, 29, 3, &mut rng);
+            let leaves = root.leaves();
+            assert!(leaves.len() > 4, "{partition} made too few leaves");
+
+            for y in 0..29 {
+                for x in 0..37 {
+                    let pos = Position { x, y };
+                    let count = leaves.iter().filter(|leaf| leaf.contains(&pos)).count();
+                    assert_eq!(count, 1, "{partition} covers {pos:?} {count} times");
+                }
+            }
+            if partition != Partition::Voronoi {
+                assert!(
+                    leaves
+                        .iter()
+                        .all(|leaf| leaf.width >= 3 && leaf.height >= 3)
+                );
+            }
+
+            let starts = PerLeaf.place(&root, 2, None, &mut rng);
+            assert_eq!(starts.len(), leaves.len() * 2);
+            assert!(
+                leaves
+                    .iter()
+                    .zip(starts.chunks(2))
+                    .all(|(leaf, starts)| starts.iter().all(|pos| leaf.contains(pos)))
+            );
+        }
+        assert_eq!("kd".parse(), Ok(Partition::KdTree));
+    }
+
+    #[test]
+    fn it_should_find_nearest_site() {
+        let mut rng = SmallRng::seed_from_u64(123);
+        let sites = scatter(41, 23, 2, &mut rng);
+        let buckets = Buckets::new(&sites, 41, 23, 4);
+
+        for y in 0..23 {
+            for x in 0..41 {
+                let cell = Position { x, y };
+                let closest = (0..sites.len()).min_by_key(|site| {
+                    sites[*site].x.abs_diff(x).pow(2) + sites[*site].y.abs_diff(y).pow(2)
+                });
+                assert_eq!(buckets.nearest(&sites, &cell), closest);
+            }
+        }
+    }
+}
@@ -0,0 +1,548 @@
+use std::{fmt::Display, str::FromStr};
+
+use rand::{Rng, rngs::SmallRng};
+
+use crate::{error::Error, map::Position, mask::Mask, quadtree::Leaf};
+
+/// Chooses where trails start, using the leaves of a quadtree
+pub trait StartPlacement {
+    /// Starting points for `root` and its leaves, on cells `mask` allows.
+    /// `density` is roughly how many to try in each leaf.
+    fn place(
+        &self,
+        root: &Leaf,
+        density: u8,
+        mask: Option<&Mask>,
+        rng: &mut SmallRng,
+    ) -> Vec<Position>;
+}
+
+fn allowed(mask: Option<&Mask>, pos: &Position) -> bool {
+    mask.is_none_or(|mask| mask.allows(pos))
+}
+
+fn random_cell(leaf: &Leaf, rng: &mut SmallRng) -> Position {
+    if leaf.region.is_some() {
+        let cells = leaf.cells();
+        return cells[rng.random_range(0..cells.len())];
+    }
+    let x = rng.random_range(leaf.x..leaf.x + leaf.width);
+    let y = rng.random_range(leaf.y..leaf.y + leaf.height);
+    Position { x, y }
+}
+
+/// `density` uniformly random cells in each leaf
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct PerLeaf;
+
+impl StartPlacement for PerLeaf {
+    fn place(
+        &self,
+        root: &Leaf,
+        density: u8,
+        mask: Option<&Mask>,
+        rng: &mut SmallRng,
+    ) -> Vec<Position> {
+        let mut starting_points = vec![];
+        match mask {
+            Some(mask) => root.add_start_masked(&mut starting_points, rng, density, mask),
+            None => root.add_start(&mut starting_points, rng, density),
+        }
+        starting_points
+    }
+}
+
+/// Random cells in each leaf, but never closer than `min_distance` cells to
+/// another start, so trails spread out evenly
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct PoissonDisk {
+    pub min_distance: f64,
+}
+
+/// Tries for each start before giving up, as in Bridson's algorithm
+const POISSON_ATTEMPTS: usize = 30;
+
+impl StartPlacement for PoissonDisk {
+    fn place(
+        &self,
+        root: &Leaf,
+        density: u8,
+        mask: Option<&Mask>,
+        rng: &mut SmallRng,
+    ) -> Vec<Position> {
+        // buckets as wide as the spacing, so anything too close is in the
+        // same bucket or one next to it
+        let size = self.min_distance.max(1.0);
+        let columns = ((root.width as f64 / size).ceil() as usize).max(1);
+        let rows = ((root.height as f64 / size).ceil() as usize).max(1);
+        let bucket = |pos: &Position| {
+            (
+                (pos.x as f64 / size) as usize,
+                (pos.y as f64 / size) as usize,
+            )
+        };
+        let mut buckets: Vec<Vec<Position>> = vec![vec![]; columns * rows];
+
+        let mut starting_points = vec![];
+        for leaf in root.leaves() {
+            for _ in 0..density {
+                for _ in 0..POISSON_ATTEMPTS {
+                    let pos = random_cell(leaf, rng);
+                    let (column, row) = bucket(&pos);
+                    let too_close = (row.saturating_sub(1)..(row + 2).min(rows))
+                        .flat_map(|row| {
+                            (column.saturating_sub(1)..(column + 2).min(columns))
+                                .map(move |column| row * columns + column)
+                        })
+                        .flat_map(|index| &buckets[index])
+                        .any(|other| {
+                            let dx = pos.x as f64 - other.x as f64;
+                            let dy = pos.y as f64 - other.y as f64;
+                            (dx * dx + dy * dy).sqrt() < self.min_distance
+                        });
+                    if !too_close && allowed(mask, &pos) {
+                        buckets[row * columns + column].push(pos);
+                        starting_points.push(pos);
+                        break;
+                    }
+                }
+            }
+        }
+        starting_points
+    }
+}
+
+/// Each leaf split into `density` equal blocks, with a random cell in each
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct JitteredGrid;
+
+impl StartPlacement for JitteredGrid {
+    fn place(
+        &self,
+        root: &Leaf,
+        density: u8,
+        mask: Option<&Mask>,
+        rng: &mut SmallRng,
+    ) -> Vec<Position> {
+        let density = density as usize;
+        let columns = (density as f64).sqrt().ceil() as usize;
+        let rows = density.div_ceil(columns.max(1));
+
+        let mut starting_points = vec![];
+        for leaf in root.leaves() {
+            for block in 0..density {
+                let (column, row) = (block % columns, block / columns);
+                // blocks of a small leaf can be less than a cell across
+                let x = leaf.x + column * leaf.width / columns;
+                let y = leaf.y + row * leaf.height / rows;
+                let block = Leaf::new(
+                    x,
+                    y,
+                    (leaf.x + (column + 1) * leaf.width / columns).max(x + 1) - x,
+                    (leaf.y + (row + 1) * leaf.height / rows).max(y + 1) - y,
+                    leaf.min_size,
+                    leaf.depth,
+                );
+                let pos = random_cell(&block, rng);
+                if leaf.contains(&pos) && allowed(mask, &pos) {
+                    starting_points.push(pos);
+                }
+            }
+        }
+        starting_points
+    }
+}
+
+/// How likely each cell of a canvas is to be a start, from 0 to 1
+#[derive(Debug, PartialEq, Clone)]
+pub struct Weights {
+    pub width: usize,
+    pub values: Vec<f64>,
+}
+
+impl Weights {
+    /// Smooth random blobs roughly `scale` cells across
+    pub fn noise(width: usize, height: usize, scale: f64, rng: &mut SmallRng) -> Self {
+        let scale = scale.max(1.0);
+        let columns = (width as f64 / scale) as usize + 2;
+        let rows = (height as f64 / scale) as usize + 2;
+        let lattice: Vec<f64> = (0..columns * rows).map(|_| rng.random()).collect();
+
+        let smooth = |t: f64| t * t * (3.0 - 2.0 * t);
+        let values = (0..width * height)
+            .map(|index| {
+                let x = (index % width) as f64 / scale;
+                let y = (index / width) as f64 / scale;
+                let (column, row) = (x as usize, y as usize);
+                let (tx, ty) = (smooth(x.fract()), smooth(y.fract()));
+                let at = |column: usize, row: usize| lattice[row * columns + column];
+                let top = at(column, row) + (at(column + 1, row) - at(column, row)) * tx;
+                let bottom =
+                    at(column, row + 1) + (at(column + 1, row + 1) - at(column, row + 1)) * tx;
+                top + (bottom - top) * ty
+            })
+            .collect();
+        Weights { width, values }
+    }
+
+    fn weight(&self, pos: &Position) -> f64 {
+        self.values
+            .get(pos.y * self.width + pos.x)
+            .copied()
+            .unwrap_or(0.0)
+    }
+}
+
+/// Random cells in each leaf, each kept with the chance given by `weights`,
+/// so high weights get more trails
+#[derive(Debug, PartialEq, Clone)]
+pub struct Weighted {
+    pub weights: Weights,
+}
+
+impl StartPlacement for Weighted {
+    fn place(
+        &self,
+        root: &Leaf,
+        density: u8,
+        mask: Option<&Mask>,
+        rng: &mut SmallRng,
+    ) -> Vec<Position> {
+        let mut starting_points = vec![];
+        for leaf in root.leaves() {
+            for _ in 0..density {
+                let pos = random_cell(leaf, rng);
+                let weight = self.weights.weight(&pos).clamp(0.0, 1.0);
+                if rng.random_bool(weight) && allowed(mask, &pos) {
+                    starting_points.push(pos);
+                }
+            }
+        }
+        starting_points
+    }
+}
+
+/// Exactly these cells, ignoring leaves and density. Cells off the canvas
+/// or outside the mask are skipped.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Coordinates(pub Vec<Position>);
+
+impl StartPlacement for Coordinates {
+    fn place(
+        &self,
+        root: &Leaf,
+        _density: u8,
+        mask: Option<&Mask>,
+        _rng: &mut SmallRng,
+    ) -> Vec<Position> {
+        self.0
+            .iter()
+            .filter(|pos| root.contains(pos) && allowed(mask, pos))
+            .copied()
+            .collect()
+    }
+}
+
+/// Greyscale image, with brightness from 0 (black) to 1 (white)
+#[derive(Debug, PartialEq, Clone)]
+pub struct Greyscale {
+    pub width: usize,
+    pub height: usize,
+    pub values: Vec<f64>,
+}
+
+impl Greyscale {
+    /// Read a PGM image, either plain (`P2`) or binary (`P5`)
+    pub fn pgm(bytes: &[u8]) -> Result<Self, Error> {
+        let invalid = |message: &str| Error::Image(message.to_string());
+
+        let mut fields = Fields { bytes, pos: 0 };
+        let binary = match fields.next() {
+            Some("P2") => false,
+            Some("P5") => true,
+            _ => return Err(invalid("Not a PGM image")),
+        };
+        let mut number = || fields.next().and_then(|field| field.parse::<usize>().ok());
+        let (Some(width), Some(height), Some(max)) = (number(), number(), number()) else {
+            return Err(invalid("Missing width, height or maximum value"));
+        };
+        if width == 0 || height == 0 || !(1..=65535).contains(&max) {
+            return Err(invalid("Invalid width, height or maximum value"));
+        }
+
+        let len = width.checked_mul(height).ok_or(Error::Overflow)?;
+        let values: Vec<usize> = match binary {
+            true => {
+                // a single whitespace character separates header and pixels
+                let pixels = bytes.get(fields.pos + 1..).unwrap_or_default();
+                match max < 256 {
+                    true => pixels.iter().map(|b| *b as usize).collect(),
+                    false => pixels
+                        .chunks_exact(2)
+                        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]) as usize)
+                        .collect(),
+                }
+            }
+            false => std::iter::from_fn(number).take(len).collect(),
+        };
+        if values.len() < len {
+            return Err(invalid("Image has too few pixels"));
+        }
+
+        Ok(Greyscale {
+            width,
+            height,
+            values: values[..len]
+                .iter()
+                .map(|value| (*value).min(max) as f64 / max as f64)
+                .collect(),
+        })
+    }
+
+    /// Darker pixels give higher weights, stretched to fill the canvas
+    pub fn weights(&self, width: usize, height: usize) -> Weights {
+        let values = (0..width * height)
+            .map(|index| {
+                let column = (index % width) * self.width / width;
+                let row = (index / width) * self.height / height;
+                1.0 - self.values[row * self.width + column]
+            })
+            .collect();
+        Weights { width, values }
+    }
+}
+
+/// Fields of a PGM header, separated by whitespace with `#` comments
+struct Fields<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Fields<'a> {
+    fn next(&mut self) -> Option<&'a str> {
+        loop {
+            match self.bytes.get(self.pos) {
+                Some(b'#') => {
+                    while self.bytes.get(self.pos).is_some_and(|b| *b != b'\n') {
+                        self.pos += 1;
+                    }
+                }
+                Some(b) if b.is_ascii_whitespace() => self.pos += 1,
+                _ => break,
+            }
+        }
+        let start = self.pos;
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(|b| !b.is_ascii_whitespace())
+        {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.bytes[start..self.pos])
+            .ok()
+            .filter(|field| !field.is_empty())
+    }
+}
+
+/// Start placement chosen in `GenerationConfig`
+#[derive(Debug, PartialEq, Clone, Default)]
+pub enum Placement {
+    #[default]
+    PerLeaf,
+    PoissonDisk {
+        min_distance: f64,
+    },
+    JitteredGrid,
+    /// Weighted by noise with blobs `scale` cells across
+    Noise {
+        scale: f64,
+    },
+    /// Weighted by an image, with more trails where it's darker
+    Image(Greyscale),
+    Points(Vec<Position>),
+}
+
+impl Display for Placement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Placement::PerLeaf => write!(f, "per-leaf"),
+            Placement::PoissonDisk { min_distance } => write!(f, "poisson:{min_distance}"),
+            Placement::JitteredGrid => write!(f, "jittered"),
+            Placement::Noise { scale } => write!(f, "noise:{scale}"),
+            Placement::Image(image) => write!(f, "image:{}x{}", image.width, image.height),
+            Placement::Points(points) => {
+                let points: Vec<String> = points
+                    .iter()
+                    .map(|pos| format!("{},{}", pos.x, pos.y))
+                    .collect();
+                write!(f, "points:{}", points.join(" "))
+            }
+        }
+    }
+}
+
+impl FromStr for Placement {
+    type Err = String;
+
+    /// `per-leaf`, `poisson`, `poisson:4`, `jittered`, `noise`, `noise:12`
+    /// or `points:3,4 10,2`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, args) = s.split_once(':').unwrap_or((s, ""));
+        let size = |default: f64| match args {
+            "" => Ok(default),
+            _ => args
+                .parse::<f64>()
+                .ok()
+                .filter(|size| size.is_finite() && *size >= 1.0)
+                .ok_or_else(|| format!("Size {args:?} should be a number of cells, at least 1")),
+        };
+        match name {
+            "per-leaf" => Ok(Placement::PerLeaf),
+            "poisson" => Ok(Placement::PoissonDisk {
+                min_distance: size(3.0)?,
+            }),
+            "jittered" => Ok(Placement::JitteredGrid),
+            "noise" => Ok(Placement::Noise { scale: size(8.0)? }),
+            "points" => args
+                .split_whitespace()
+                .map(|point| {
+                    let (x, y) = point.split_once(',')?;
+                    Some(Position {
+                        x: x.parse().ok()?,
+                        y: y.parse().ok()?,
+                    })
+                })
+                .collect::<Option<Vec<Position>>>()
+                .map(Placement::Points)
+                .ok_or_else(|| format!("Points {args:?} should be x,y cells separated by spaces")),
+            _ => Err(format!(
+                "Unknown placement {s:?}, expected per-leaf, poisson, jittered, noise or points"
+            )),
+        }
+    }
+}
+
+impl StartPlacement for Placement {
+    fn place(
+        &self,
+        root: &Leaf,
+        density: u8,
+        mask: Option<&Mask>,
+        rng: &mut SmallRng,
+    ) -> Vec<Position> {
+        match self {
+            Placement::PerLeaf => PerLeaf.place(root, density, mask, rng),
+            Placement::PoissonDisk { min_distance } => PoissonDisk {
+                min_distance: *min_distance,
+            }
+            .place(root, density, mask, rng),
+            Placement::JitteredGrid => JitteredGrid.place(root, density, mask, rng),
+            Placement::Noise { scale } => Weighted {
+                weights: Weights::noise(root.width, root.height, *scale, rng),
+            }
+            .place(root, density, mask, rng),
+            Placement::Image(image) => Weighted {
+                weights: image.weights(root.width, root.height),
+            }
+            .place(root, density, mask, rng),
+            Placement::Points(points) => {
+                Coordinates(points.clone()).place(root, density, mask, rng)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand::{SeedableRng, rngs::SmallRng};
+
+    use crate::{
+        error::Error,
+        map::Position,
+        placement::{Greyscale, JitteredGrid, Placement, PoissonDisk, StartPlacement},
+        quadtree::Leaf,
+    };
+
+    fn tree(rng: &mut SmallRng) -> Leaf {
+        let mut root = Leaf::new(0, 0, 40, 30, 3, 0);
+        root.generate(rng);
+        root
+    }
+
+    #[test]
+    fn it_should_space_out_starts() {
+        let mut rng = SmallRng::seed_from_u64(123);
+        let root = tree(&mut rng);
+        let starts = PoissonDisk { min_distance: 4.0 }.place(&root, 3, None, &mut rng);
+
+        assert!(!starts.is_empty());
+        for (index, a) in starts.iter().enumerate() {
+            for b in &starts[index + 1..] {
+                let dx = a.x as f64 - b.x as f64;
+                let dy = a.y as f64 - b.y as f64;
+                assert!((dx * dx + dy * dy).sqrt() >= 4.0);
+            }
+        }
+
+        // everything is too close to the first start
+        let starts = PoissonDisk {
+            min_distance: f64::INFINITY,
+        }
+        .place(&root, 3, None, &mut rng);
+        assert_eq!(starts.len(), 1);
+    }
+
+    #[test]
+    fn it_should_jitter_starts_across_leaves() {
+        let mut rng = SmallRng::seed_from_u64(123);
+        let root = tree(&mut rng);
+        let starts = JitteredGrid.place(&root, 4, None, &mut rng);
+
+        let leaves = root.leaves();
+        assert_eq!(starts.len(), leaves.len() * 4);
+        for (index, leaf) in leaves.iter().enumerate() {
+            assert!(
+                starts[index * 4..index * 4 + 4]
+                    .iter()
+                    .all(|pos| leaf.contains(pos))
+            );
+        }
+    }
+
+    #[test]
+    fn it_should_weight_starts_by_image() {
+        // white on the left, black on the right
+        let image = Greyscale::pgm(b"P2\n# test\n2 1\n255\n255 0\n").expect("Invalid image");
+        assert_eq!(image.values, vec![1.0, 0.0]);
+        assert_eq!(Greyscale::pgm(b"P5 2 1 255\n\xff\x00"), Ok(image.clone()));
+        assert!(matches!(
+            Greyscale::pgm(b"P2 2 2 255 0"),
+            Err(Error::Image(_))
+        ));
+
+        let mut rng = SmallRng::seed_from_u64(123);
+        let root = tree(&mut rng);
+        let starts = Placement::Image(image).place(&root, 4, None, &mut rng);
+        assert!(!starts.is_empty());
+        assert!(starts.iter().all(|pos| pos.x >= 20));
+    }
+
+    #[test]
+    fn it_should_parse_placements() {
+        assert_eq!(
+            "poisson:2.5".parse(),
+            Ok(Placement::PoissonDisk { min_distance: 2.5 })
+        );
+        assert_eq!("noise".parse(), Ok(Placement::Noise { scale: 8.0 }));
+        assert!("noise:0".parse::<Placement>().is_err());
+
+        let points: Placement = "points:3,4 50,2".parse().expect("Invalid points");
+        assert_eq!(points.to_string(), "points:3,4 50,2");
+        let mut rng = SmallRng::seed_from_u64(123);
+        assert_eq!(
+            points.place(&Leaf::new(0, 0, 40, 30, 3, 0), 2, None, &mut rng),
+            vec![Position { x: 3, y: 4 }]
+        );
+    }
+}
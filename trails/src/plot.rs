@@ -0,0 +1,266 @@
+use std::f64::consts::TAU;
+
+use crate::{
+    curve::Point,
+    svg::{Marker, TrailShape},
+};
+
+/// Continuous pen down line
+pub type Polyline = Vec<Point>;
+
+/// Sides used to approximate the start circle
+pub const CIRCLE_SEGMENTS: usize = 24;
+
+/// Marker outline as a closed line, circles are approximated by a polygon
+fn outline(marker: Marker, end_radius: f64) -> Polyline {
+    match marker {
+        Marker::Start((x, y)) => (0..=CIRCLE_SEGMENTS)
+            .map(|i| {
+                let angle = TAU * i as f64 / CIRCLE_SEGMENTS as f64;
+                (x + end_radius * angle.cos(), y + end_radius * angle.sin())
+            })
+            .collect(),
+        Marker::End((x, y)) => vec![
+            (x - end_radius, y - end_radius),
+            (x + end_radius, y - end_radius),
+            (x + end_radius, y + end_radius),
+            (x - end_radius, y + end_radius),
+            (x - end_radius, y - end_radius),
+        ],
+    }
+}
+
+/// Trail lines and marker outlines in drawing order, with each marker next
+/// to the end of the line it belongs to
+pub fn strokes(shapes: &[TrailShape], end_radius: f64) -> Vec<Polyline> {
+    shapes
+        .iter()
+        .flat_map(|shape| {
+            let head = shape.head.map(|marker| outline(marker, end_radius));
+            let tail = shape.tail.map(|marker| outline(marker, end_radius));
+            head.into_iter().chain([shape.path.clone()]).chain(tail)
+        })
+        .collect()
+}
+
+/// Maps drawing units to plotter millimetres, flipping y so the origin is
+/// bottom left like most plotters
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct Transform {
+    scale: f64,
+    height: f64,
+}
+
+impl Transform {
+    /// Largest scale which fits the drawing inside the paper
+    fn fit(width: f64, height: f64, width_mm: f64, height_mm: f64) -> Self {
+        let scale = (width_mm / width).min(height_mm / height);
+        Transform { scale, height }
+    }
+
+    fn apply(&self, point: Point) -> Point {
+        (point.0 * self.scale, (self.height - point.1) * self.scale)
+    }
+}
+
+/// G-code for pen plotters. Pen commands vary by machine (servo, Z axis,
+/// solenoid) so they're passed through as written.
+#[derive(Debug, PartialEq, Clone)]
+pub struct GCode {
+    pub pen_up: String,
+    pub pen_down: String,
+    /// Drawing speed in mm/min
+    pub feed_rate: f64,
+    /// Pen up speed in mm/min
+    pub travel_rate: f64,
+    pub width_mm: f64,
+    pub height_mm: f64,
+}
+
+impl Default for GCode {
+    fn default() -> Self {
+        GCode {
+            pen_up: String::from("G0 Z5"),
+            pen_down: String::from("G1 Z0"),
+            feed_rate: 3000.0,
+            travel_rate: 6000.0,
+            width_mm: 200.0,
+            height_mm: 200.0,
+        }
+    }
+}
+
+impl GCode {
+    /// `width` and `height` are the drawing size, eg `Svg::width`
+    pub fn export(&self, strokes: &[Polyline], width: f64, height: f64) -> String {
+        let transform = Transform::fit(width, height, self.width_mm, self.height_mm);
+        let mut output = String::from("G21\nG90\n");
+        output += &format!("{}\n", self.pen_up);
+
+        for stroke in strokes.iter().filter(|stroke| !stroke.is_empty()) {
+            let (x, y) = transform.apply(stroke[0]);
+            output += &format!("G0 X{x:.3} Y{y:.3} F{}\n", self.travel_rate);
+            output += &format!("{}\n", self.pen_down);
+            for point in &stroke[1..] {
+                let (x, y) = transform.apply(*point);
+                output += &format!("G1 X{x:.3} Y{y:.3} F{}\n", self.feed_rate);
+            }
+            output += &format!("{}\n", self.pen_up);
+        }
+
+        output += &format!("G0 X0 Y0 F{}\n", self.travel_rate);
+        output
+    }
+}
+
+/// HP-GL plotter units per millimetre
+const HPGL_UNITS: f64 = 40.0;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Hpgl {
+    pub pen: u8,
+    pub width_mm: f64,
+    pub height_mm: f64,
+}
+
+impl Default for Hpgl {
+    fn default() -> Self {
+        Hpgl {
+            pen: 1,
+            width_mm: 200.0,
+            height_mm: 200.0,
+        }
+    }
+}
+
+impl Hpgl {
+    /// `width` and `height` are the drawing size, eg `Svg::width`
+    pub fn export(&self, strokes: &[Polyline], width: f64, height: f64) -> String {
+        let transform = Transform::fit(width, height, self.width_mm, self.height_mm);
+        let units = |point: Point| {
+            let (x, y) = transform.apply(point);
+            format!(
+                "{},{}",
+                (x * HPGL_UNITS).round() as i64,
+                (y * HPGL_UNITS).round() as i64
+            )
+        };
+
+        let mut output = format!("IN;SP{};", self.pen);
+        for stroke in strokes.iter().filter(|stroke| !stroke.is_empty()) {
+            output += &format!("PU{};", units(stroke[0]));
+            if stroke.len() > 1 {
+                let points: Vec<String> = stroke[1..].iter().map(|point| units(*point)).collect();
+                output += &format!("PD{};", points.join(","));
+            }
+        }
+        output += "PU;SP0;";
+        output
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        map::Map,
+        plot::{CIRCLE_SEGMENTS, GCode, Hpgl, strokes},
+        svg::{Marker, Svg, TrailShape},
+    };
+
+    fn shapes() -> (Svg<'static>, Vec<TrailShape>) {
+        let mut map = Map::parse("01234\n98765").expect("Failed to parse");
+        map.find_all_paths(10).expect("Too many paths");
+        let svg = Svg::new(64, 32, map.width, map.height, 2, "black", 10);
+        let shapes = svg.shapes(&map).expect("Failed to get shapes");
+        (svg, shapes)
+    }
+
+    #[test]
+    fn it_should_share_svg_geometry() {
+        let (_, shapes) = shapes();
+        assert_eq!(
+            shapes,
+            vec![TrailShape {
+                path: vec![(42.0, 32.0), (288.0, 32.0), (288.0, 96.0), (42.0, 96.0)],
+                head: Some(Marker::Start((32.0, 32.0))),
+                tail: Some(Marker::End((32.0, 96.0))),
+            }]
+        );
+    }
+
+    #[test]
+    fn it_should_approximate_markers() {
+        let (_, shapes) = shapes();
+        let strokes = strokes(&shapes, 10.0);
+
+        assert_eq!(strokes.len(), 3);
+        let circle = &strokes[0];
+        assert_eq!(circle.len(), CIRCLE_SEGMENTS + 1);
+        assert!(circle.iter().all(|(x, y)| {
+            let dist = ((x - 32.0).powi(2) + (y - 32.0).powi(2)).sqrt();
+            (dist - 10.0).abs() < 1e-9
+        }));
+        assert_eq!(strokes[1], shapes[0].path);
+        assert_eq!(
+            strokes[2],
+            vec![
+                (22.0, 86.0),
+                (42.0, 86.0),
+                (42.0, 106.0),
+                (22.0, 106.0),
+                (22.0, 86.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn it_should_export_gcode() {
+        let (svg, shapes) = shapes();
+        let gcode = GCode {
+            width_mm: 100.0,
+            height_mm: 100.0,
+            ..GCode::default()
+        };
+        let output = gcode.export(
+            &[shapes[0].path.clone()],
+            svg.width as f64,
+            svg.height as f64,
+        );
+
+        // 320 wide drawing into 100mm, y flipped from 128 high
+        assert_eq!(
+            output,
+            "G21
+G90
+G0 Z5
+G0 X13.125 Y30.000 F6000
+G1 Z0
+G1 X90.000 Y30.000 F3000
+G1 X90.000 Y10.000 F3000
+G1 X13.125 Y10.000 F3000
+G0 Z5
+G0 X0 Y0 F6000
+"
+        );
+    }
+
+    #[test]
+    fn it_should_export_hpgl() {
+        let (svg, shapes) = shapes();
+        let hpgl = Hpgl {
+            width_mm: 100.0,
+            height_mm: 100.0,
+            ..Hpgl::default()
+        };
+        let output = hpgl.export(
+            &[shapes[0].path.clone()],
+            svg.width as f64,
+            svg.height as f64,
+        );
+
+        assert_eq!(
+            output,
+            "IN;SP1;PU525,1200;PD3600,1200,3600,400,525,400;PU;SP0;"
+        );
+    }
+}
@@ -1,8 +1,41 @@
 use rand::{Rng, rngs::SmallRng};
 
-use crate::map::Position;
+use crate::{map::Position, mask::Mask};
 
-#[derive(Debug, PartialEq)]
+/// When leaves split
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SplitRules {
+    /// Chance of a leaf splitting once it's deeper than `forced_depth`
+    pub probability: f64,
+    /// Leaves this deep or shallower always split, otherwise it'll be boring
+    pub forced_depth: usize,
+    /// Leaves this deep never split
+    pub max_depth: Option<usize>,
+}
+
+impl Default for SplitRules {
+    fn default() -> Self {
+        SplitRules {
+            probability: 0.85,
+            forced_depth: 2,
+            max_depth: None,
+        }
+    }
+}
+
+impl SplitRules {
+    /// Whether a leaf at `depth` should split, if it's big enough
+    pub fn should_split(&self, depth: usize, rng: &mut SmallRng) -> bool {
+        let should_split = if depth > self.forced_depth {
+            rng.random_bool(self.probability)
+        } else {
+            true
+        };
+        should_split && self.max_depth.is_none_or(|max| depth < max)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
 pub struct Leaf {
     pub depth: usize,
     pub min_size: usize,
@@ -10,6 +43,9 @@ pub struct Leaf {
     pub y: usize,
     pub width: usize,
     pub height: usize,
+    /// Which cells of the bounding box are in the leaf, row by row, for
+    /// leaves which aren't rectangles
+    pub region: Option<Vec<bool>>,
     pub children: Vec<Leaf>,
 }
 
@@ -29,25 +65,19 @@ impl Leaf {
             width,
             height,
             min_size,
+            region: None,
             children: vec![],
         }
     }
 
-    fn split(&mut self, rng: &mut SmallRng) -> bool {
-        // if over size and is big enough to split
-        // if depth < 2 always split otherwise it'll be boring
-        // otherwise 75% chance to split
-
-        // usually split
-        // TODO base depth on size
-        let should_split = if self.depth > 2 {
-            rng.random_bool(0.85)
-        } else {
-            true
-        };
+    fn split(&mut self, rules: &SplitRules, rng: &mut SmallRng) -> bool {
+        // split the smaller halves at the top and left, so children exactly
+        // cover the parent
+        let should_split = rules.should_split(self.depth, rng);
         if should_split && self.width / 2 >= self.min_size && self.height / 2 >= self.min_size {
             let horz_mid = self.width / 2;
             let vert_mid = self.height / 2;
+            let (east, south) = (self.width - horz_mid, self.height - vert_mid);
             let north_west = Leaf::new(
                 self.x,
                 self.y,
@@ -59,7 +89,7 @@ impl Leaf {
             let north_east = Leaf::new(
                 self.x + horz_mid,
                 self.y,
-                horz_mid,
+                east,
                 vert_mid,
                 self.min_size,
                 self.depth + 1,
@@ -67,8 +97,8 @@ impl Leaf {
             let south_east = Leaf::new(
                 self.x + horz_mid,
                 self.y + vert_mid,
-                horz_mid,
-                vert_mid,
+                east,
+                south,
                 self.min_size,
                 self.depth + 1,
             );
@@ -76,7 +106,7 @@ impl Leaf {
                 self.x,
                 self.y + vert_mid,
                 horz_mid,
-                vert_mid,
+                south,
                 self.min_size,
                 self.depth + 1,
             );
@@ -97,18 +127,87 @@ impl Leaf {
     }
 
     pub fn generate(&mut self, rng: &mut SmallRng) {
-        if self.can_split() && self.split(rng) {
+        self.generate_with(&SplitRules::default(), rng);
+    }
+
+    /// Split leaves recursively, following `rules`
+    pub fn generate_with(&mut self, rules: &SplitRules, rng: &mut SmallRng) {
+        if self.can_split() && self.split(rules, rng) {
             self.children
                 .iter_mut()
-                .for_each(|child| child.generate(rng));
+                .for_each(|child| child.generate_with(rules, rng));
         }
     }
 
+    /// Every leaf without children, in depth first order
+    pub fn leaves(&self) -> Vec<&Leaf> {
+        if self.children.is_empty() {
+            return vec![self];
+        }
+        self.children
+            .iter()
+            .flat_map(|child| child.leaves())
+            .collect()
+    }
+
+    pub fn contains(&self, pos: &Position) -> bool {
+        pos.x >= self.x
+            && pos.x < self.x + self.width
+            && pos.y >= self.y
+            && pos.y < self.y + self.height
+            && self
+                .region
+                .as_ref()
+                .is_none_or(|region| region[(pos.y - self.y) * self.width + pos.x - self.x])
+    }
+
+    /// Every cell in the leaf, row by row
+    pub fn cells(&self) -> Vec<Position> {
+        (self.y..self.y + self.height)
+            .flat_map(|y| (self.x..self.x + self.width).map(move |x| Position { x, y }))
+            .filter(|pos| self.contains(pos))
+            .collect()
+    }
+
     pub fn add_start(&self, starting_points: &mut Vec<Position>, rng: &mut SmallRng, density: u8) {
+        self.place_starts(starting_points, rng, density, None);
+    }
+
+    /// As `add_start`, but only on cells the mask allows. Leaves entirely
+    /// outside the mask get no starts.
+    pub fn add_start_masked(
+        &self,
+        starting_points: &mut Vec<Position>,
+        rng: &mut SmallRng,
+        density: u8,
+        mask: &Mask,
+    ) {
+        self.place_starts(starting_points, rng, density, Some(mask));
+    }
+
+    fn place_starts(
+        &self,
+        starting_points: &mut Vec<Position>,
+        rng: &mut SmallRng,
+        density: u8,
+        mask: Option<&Mask>,
+    ) {
         if !self.children.is_empty() {
             self.children.iter().for_each(|child| {
-                child.add_start(starting_points, rng, density);
+                child.place_starts(starting_points, rng, density, mask);
             });
+        } else if mask.is_some() || self.region.is_some() {
+            let cells: Vec<Position> = self
+                .cells()
+                .into_iter()
+                .filter(|pos| mask.is_none_or(|mask| mask.allows(pos)))
+                .collect();
+            if cells.is_empty() {
+                return;
+            }
+            for _ in 0..density {
+                starting_points.push(cells[rng.random_range(0..cells.len())]);
+            }
         } else {
             for _ in 0..density {
                 let x = rng.random_range(self.x..self.x + self.width);
@@ -125,9 +224,12 @@ impl Leaf {
 
 #[cfg(test)]
 mod test {
-    use rand::{SeedableRng, rngs::SmallRng};
+    use rand::{Rng, SeedableRng, rngs::SmallRng};
 
-    use crate::{map::Position, quadtree::Leaf};
+    use crate::{
+        map::Position,
+        quadtree::{Leaf, SplitRules},
+    };
 
     #[test]
     fn it_should_create_new_tree() {
@@ -141,6 +243,7 @@ mod test {
                 y: 0,
                 width: 6,
                 height: 6,
+                region: None,
                 children: vec![]
             }
         );
@@ -162,6 +265,7 @@ mod test {
                 y: 0,
                 width: 16,
                 height: 16,
+                region: None,
                 children: vec![
                     Leaf {
                         depth: 1,
@@ -170,6 +274,7 @@ mod test {
                         y: 0,
                         width: 8,
                         height: 8,
+                        region: None,
                         children: vec![
                             Leaf {
                                 depth: 2,
@@ -178,6 +283,7 @@ mod test {
                                 y: 0,
                                 width: 4,
                                 height: 4,
+                                region: None,
                                 children: vec![
                                     Leaf {
                                         depth: 3,
@@ -186,6 +292,7 @@ mod test {
                                         y: 0,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -195,6 +302,7 @@ mod test {
                                         y: 0,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -204,6 +312,7 @@ mod test {
                                         y: 2,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -213,6 +322,7 @@ mod test {
                                         y: 2,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     }
                                 ]
@@ -224,6 +334,7 @@ mod test {
                                 y: 0,
                                 width: 4,
                                 height: 4,
+                                region: None,
                                 children: vec![
                                     Leaf {
                                         depth: 3,
@@ -232,6 +343,7 @@ mod test {
                                         y: 0,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -241,6 +353,7 @@ mod test {
                                         y: 0,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -250,6 +363,7 @@ mod test {
                                         y: 2,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -259,6 +373,7 @@ mod test {
                                         y: 2,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     }
                                 ]
@@ -270,6 +385,7 @@ mod test {
                                 y: 4,
                                 width: 4,
                                 height: 4,
+                                region: None,
                                 children: vec![
                                     Leaf {
                                         depth: 3,
@@ -278,6 +394,7 @@ mod test {
                                         y: 4,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -287,6 +404,7 @@ mod test {
                                         y: 4,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -296,6 +414,7 @@ mod test {
                                         y: 6,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -305,6 +424,7 @@ mod test {
                                         y: 6,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     }
                                 ]
@@ -316,6 +436,7 @@ mod test {
                                 y: 4,
                                 width: 4,
                                 height: 4,
+                                region: None,
                                 children: vec![
                                     Leaf {
                                         depth: 3,
@@ -324,6 +445,7 @@ mod test {
                                         y: 4,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -333,6 +455,7 @@ mod test {
                                         y: 4,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -342,6 +465,7 @@ mod test {
                                         y: 6,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -351,6 +475,7 @@ mod test {
                                         y: 6,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     }
                                 ]
@@ -364,6 +489,7 @@ mod test {
                         y: 0,
                         width: 8,
                         height: 8,
+                        region: None,
                         children: vec![
                             Leaf {
                                 depth: 2,
@@ -372,6 +498,7 @@ mod test {
                                 y: 0,
                                 width: 4,
                                 height: 4,
+                                region: None,
                                 children: vec![
                                     Leaf {
                                         depth: 3,
@@ -380,6 +507,7 @@ mod test {
                                         y: 0,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -389,6 +517,7 @@ mod test {
                                         y: 0,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -398,6 +527,7 @@ mod test {
                                         y: 2,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -407,6 +537,7 @@ mod test {
                                         y: 2,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     }
                                 ]
@@ -418,6 +549,7 @@ mod test {
                                 y: 0,
                                 width: 4,
                                 height: 4,
+                                region: None,
                                 children: vec![
                                     Leaf {
                                         depth: 3,
@@ -426,6 +558,7 @@ mod test {
                                         y: 0,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -435,6 +568,7 @@ mod test {
                                         y: 0,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -444,6 +578,7 @@ mod test {
                                         y: 2,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -453,6 +588,7 @@ mod test {
                                         y: 2,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     }
                                 ]
@@ -464,6 +600,7 @@ mod test {
                                 y: 4,
                                 width: 4,
                                 height: 4,
+                                region: None,
                                 children: vec![
                                     Leaf {
                                         depth: 3,
@@ -472,6 +609,7 @@ mod test {
                                         y: 4,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -481,6 +619,7 @@ mod test {
                                         y: 4,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -490,6 +629,7 @@ mod test {
                                         y: 6,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -499,6 +639,7 @@ mod test {
                                         y: 6,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     }
                                 ]
@@ -510,6 +651,7 @@ mod test {
                                 y: 4,
                                 width: 4,
                                 height: 4,
+                                region: None,
                                 children: vec![
                                     Leaf {
                                         depth: 3,
@@ -518,6 +660,7 @@ mod test {
                                         y: 4,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -527,6 +670,7 @@ mod test {
                                         y: 4,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -536,6 +680,7 @@ mod test {
                                         y: 6,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -545,6 +690,7 @@ mod test {
                                         y: 6,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     }
                                 ]
@@ -558,6 +704,7 @@ mod test {
                         y: 8,
                         width: 8,
                         height: 8,
+                        region: None,
                         children: vec![
                             Leaf {
                                 depth: 2,
@@ -566,6 +713,7 @@ mod test {
                                 y: 8,
                                 width: 4,
                                 height: 4,
+                                region: None,
                                 children: vec![
                                     Leaf {
                                         depth: 3,
@@ -574,6 +722,7 @@ mod test {
                                         y: 8,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -583,6 +732,7 @@ mod test {
                                         y: 8,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -592,6 +742,7 @@ mod test {
                                         y: 10,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -601,6 +752,7 @@ mod test {
                                         y: 10,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     }
                                 ]
@@ -612,6 +764,7 @@ mod test {
                                 y: 8,
                                 width: 4,
                                 height: 4,
+                                region: None,
                                 children: vec![
                                     Leaf {
                                         depth: 3,
@@ -620,6 +773,7 @@ mod test {
                                         y: 8,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -629,6 +783,7 @@ mod test {
                                         y: 8,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -638,6 +793,7 @@ mod test {
                                         y: 10,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -647,6 +803,7 @@ mod test {
                                         y: 10,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     }
                                 ]
@@ -658,6 +815,7 @@ mod test {
                                 y: 12,
                                 width: 4,
                                 height: 4,
+                                region: None,
                                 children: vec![
                                     Leaf {
                                         depth: 3,
@@ -666,6 +824,7 @@ mod test {
                                         y: 12,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -675,6 +834,7 @@ mod test {
                                         y: 12,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -684,6 +844,7 @@ mod test {
                                         y: 14,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -693,6 +854,7 @@ mod test {
                                         y: 14,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     }
                                 ]
@@ -704,6 +866,7 @@ mod test {
                                 y: 12,
                                 width: 4,
                                 height: 4,
+                                region: None,
                                 children: vec![
                                     Leaf {
                                         depth: 3,
@@ -712,6 +875,7 @@ mod test {
                                         y: 12,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -721,6 +885,7 @@ mod test {
                                         y: 12,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -730,6 +895,7 @@ mod test {
                                         y: 14,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -739,6 +905,7 @@ mod test {
                                         y: 14,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     }
                                 ]
@@ -752,6 +919,7 @@ mod test {
                         y: 8,
                         width: 8,
                         height: 8,
+                        region: None,
                         children: vec![
                             Leaf {
                                 depth: 2,
@@ -760,6 +928,7 @@ mod test {
                                 y: 8,
                                 width: 4,
                                 height: 4,
+                                region: None,
                                 children: vec![
                                     Leaf {
                                         depth: 3,
@@ -768,6 +937,7 @@ mod test {
                                         y: 8,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -777,6 +947,7 @@ mod test {
                                         y: 8,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -786,6 +957,7 @@ mod test {
                                         y: 10,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -795,6 +967,7 @@ mod test {
                                         y: 10,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     }
                                 ]
@@ -806,6 +979,7 @@ mod test {
                                 y: 8,
                                 width: 4,
                                 height: 4,
+                                region: None,
                                 children: vec![
                                     Leaf {
                                         depth: 3,
@@ -814,6 +988,7 @@ mod test {
                                         y: 8,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -823,6 +998,7 @@ mod test {
                                         y: 8,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -832,6 +1008,7 @@ mod test {
                                         y: 10,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -841,6 +1018,7 @@ mod test {
                                         y: 10,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     }
                                 ]
@@ -852,6 +1030,7 @@ mod test {
                                 y: 12,
                                 width: 4,
                                 height: 4,
+                                region: None,
                                 children: vec![
                                     Leaf {
                                         depth: 3,
@@ -860,6 +1039,7 @@ mod test {
                                         y: 12,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -869,6 +1049,7 @@ mod test {
                                         y: 12,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -878,6 +1059,7 @@ mod test {
                                         y: 14,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -887,6 +1069,7 @@ mod test {
                                         y: 14,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     }
                                 ]
@@ -898,6 +1081,7 @@ mod test {
                                 y: 12,
                                 width: 4,
                                 height: 4,
+                                region: None,
                                 children: vec![
                                     Leaf {
                                         depth: 3,
@@ -906,6 +1090,7 @@ mod test {
                                         y: 12,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -915,6 +1100,7 @@ mod test {
                                         y: 12,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -924,6 +1110,7 @@ mod test {
                                         y: 14,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     },
                                     Leaf {
@@ -933,6 +1120,7 @@ mod test {
                                         y: 14,
                                         width: 2,
                                         height: 2,
+                                        region: None,
                                         children: vec![]
                                     }
                                 ]
@@ -944,6 +1132,30 @@ mod test {
         );
     }
 
+    #[test]
+    fn it_should_split_rectangle() {
+        let mut root = Leaf::new(0, 0, 8, 4, 2, 0);
+        let seed = 123;
+        let mut rng = SmallRng::seed_from_u64(seed);
+        root.generate(&mut rng);
+
+        let children: Vec<(usize, usize, usize, usize)> = root
+            .children
+            .iter()
+            .map(|child| (child.x, child.y, child.width, child.height))
+            .collect();
+        assert_eq!(
+            children,
+            vec![(0, 0, 4, 2), (4, 0, 4, 2), (4, 2, 4, 2), (0, 2, 4, 2)]
+        );
+        assert!(root.children.iter().all(|child| child.children.is_empty()));
+
+        let mut starting_points = Vec::new();
+        root.add_start(&mut starting_points, &mut rng, 3);
+        assert_eq!(starting_points.len(), 12);
+        assert!(starting_points.iter().all(|pos| pos.x < 8 && pos.y < 4));
+    }
+
     #[test]
     fn it_should_add_start_nodes() {
         let root = Leaf {
@@ -953,6 +1165,7 @@ mod test {
             y: 0,
             width: 6,
             height: 6,
+            region: None,
             children: vec![
                 Leaf {
                     depth: 1,
@@ -961,6 +1174,7 @@ mod test {
                     y: 0,
                     width: 3,
                     height: 3,
+                    region: None,
                     children: vec![],
                 },
                 Leaf {
@@ -970,6 +1184,7 @@ mod test {
                     y: 0,
                     width: 3,
                     height: 3,
+                    region: None,
                     children: vec![],
                 },
             ],
@@ -990,4 +1205,79 @@ mod test {
             ]
         );
     }
+
+    /// How many leaves each cell of the canvas is in
+    fn coverage(root: &Leaf) -> Vec<usize> {
+        let mut coverage = vec![0; root.width * root.height];
+        for leaf in root.leaves() {
+            for pos in leaf.cells() {
+                coverage[pos.y * root.width + pos.x] += 1;
+            }
+        }
+        coverage
+    }
+
+    #[test]
+    fn it_should_cover_odd_sizes() {
+        let mut root = Leaf::new(0, 0, 45, 45, 3, 0);
+        let mut rng = SmallRng::seed_from_u64(123);
+        root.generate(&mut rng);
+
+        assert!(coverage(&root).iter().all(|count| *count == 1));
+        let children: Vec<(usize, usize)> = root
+            .children
+            .iter()
+            .map(|child| (child.width, child.height))
+            .collect();
+        assert_eq!(children, vec![(22, 22), (23, 22), (23, 23), (22, 23)]);
+    }
+
+    #[test]
+    fn it_should_partition_any_canvas() {
+        let mut rng = SmallRng::seed_from_u64(123);
+        for _ in 0..300 {
+            let rules = SplitRules {
+                probability: rng.random(),
+                forced_depth: rng.random_range(0..4),
+                max_depth: rng.random_bool(0.5).then(|| rng.random_range(0..6)),
+            };
+            let (width, height) = (rng.random_range(1..60), rng.random_range(1..60));
+            let min_size = rng.random_range(1..6);
+            let mut root = Leaf::new(0, 0, width, height, min_size, 0);
+            root.generate_with(&rules, &mut rng);
+
+            assert!(
+                coverage(&root).iter().all(|count| *count == 1),
+                "{width}x{height} with {rules:?} doesn't cover the canvas once"
+            );
+            for leaf in root.leaves() {
+                assert!(rules.max_depth.is_none_or(|max| leaf.depth <= max));
+                if leaf.depth > 0 {
+                    assert!(leaf.width >= min_size && leaf.height >= min_size);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn it_should_follow_split_rules() {
+        let mut rng = SmallRng::seed_from_u64(123);
+        let mut root = Leaf::new(0, 0, 64, 64, 1, 0);
+        let rules = SplitRules {
+            probability: 0.0,
+            forced_depth: 1,
+            max_depth: None,
+        };
+        root.generate_with(&rules, &mut rng);
+        assert_eq!(root.leaves().len(), 16);
+
+        let mut root = Leaf::new(0, 0, 64, 64, 1, 0);
+        let rules = SplitRules {
+            probability: 1.0,
+            forced_depth: 0,
+            max_depth: Some(3),
+        };
+        root.generate_with(&rules, &mut rng);
+        assert_eq!(root.leaves().len(), 64);
+    }
 }
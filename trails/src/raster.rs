@@ -0,0 +1,133 @@
+use resvg::{tiny_skia, usvg};
+
+use crate::error::Error;
+
+/// Largest image which will be allocated, 100 megapixels
+pub const MAX_PIXELS: u64 = 100_000_000;
+
+/// Output size for a rasterised drawing
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RasterSize {
+    /// Multiple of the SVG viewBox, where one unit is one pixel
+    Scale(f32),
+    /// Exact pixel width, height keeps the aspect ratio
+    Width(u32),
+    /// Dots per inch, treating one SVG unit as 1/96 inch like browsers do
+    Dpi(f32),
+    /// Multiple of the SVG viewBox, shrunk if needed so the image has at
+    /// most `max_pixels`
+    Fit { scale: f32, max_pixels: u64 },
+}
+
+/// Render an SVG drawing to PNG bytes with a transparent background
+pub fn to_png(svg: &str, size: RasterSize) -> Result<Vec<u8>, Error> {
+    let tree = usvg::Tree::from_str(svg, &usvg::Options::default())
+        .map_err(|err| Error::Render(err.to_string()))?;
+    let view = tree.size();
+
+    let scale = match size {
+        RasterSize::Scale(scale) => scale,
+        RasterSize::Width(width) => width as f32 / view.width(),
+        RasterSize::Dpi(dpi) => dpi / 96.0,
+        RasterSize::Fit { scale, max_pixels } => {
+            // largest scale where (w * s + 1) * (h * s + 1) <= max_pixels, so
+            // rounding the sides up still fits
+            let (w, h) = (view.width() as f64, view.height() as f64);
+            let max = max_pixels.min(MAX_PIXELS) as f64;
+            let fit =
+                (-(w + h) + ((w + h).powi(2) + 4.0 * w * h * (max - 1.0)).sqrt()) / (2.0 * w * h);
+            scale.min(fit as f32)
+        }
+    };
+    if !scale.is_finite() || scale <= 0.0 {
+        return Err(Error::Render(format!("Invalid scale {scale}")));
+    }
+
+    let width = (view.width() * scale).ceil() as u64;
+    let height = (view.height() * scale).ceil() as u64;
+    width
+        .checked_mul(height)
+        .filter(|pixels| *pixels <= MAX_PIXELS)
+        .ok_or(Error::Overflow)?;
+
+    let mut pixmap = tiny_skia::Pixmap::new(width as u32, height as u32)
+        .ok_or_else(|| Error::Render(format!("Can't create {width}x{height} image")))?;
+    resvg::render(
+        &tree,
+        tiny_skia::Transform::from_scale(scale, scale),
+        &mut pixmap.as_mut(),
+    );
+
+    pixmap
+        .encode_png()
+        .map_err(|err| Error::Render(err.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        error::Error,
+        map::Map,
+        raster::{RasterSize, to_png},
+        svg::Svg,
+    };
+
+    fn svg() -> String {
+        let mut map = Map::parse("01234\n98765").expect("Failed to parse");
+        map.find_all_paths(10).expect("Too many paths");
+        Svg::new(64, 32, map.width, map.height, 2, "black", 10)
+            .draw(&map)
+            .expect("Failed to draw")
+    }
+
+    fn dimensions(png: &[u8]) -> (u32, u32) {
+        // width and height are the first fields of the IHDR chunk
+        let width = u32::from_be_bytes([png[16], png[17], png[18], png[19]]);
+        let height = u32::from_be_bytes([png[20], png[21], png[22], png[23]]);
+        (width, height)
+    }
+
+    #[test]
+    fn it_should_render_png() {
+        let png = to_png(&svg(), RasterSize::Scale(1.0)).expect("Failed to render");
+        assert_eq!(&png[1..4], b"PNG");
+        assert_eq!(dimensions(&png), (320, 128));
+
+        let png = to_png(&svg(), RasterSize::Scale(2.5)).expect("Failed to render");
+        assert_eq!(dimensions(&png), (800, 320));
+
+        let png = to_png(&svg(), RasterSize::Width(160)).expect("Failed to render");
+        assert_eq!(dimensions(&png), (160, 64));
+
+        let png = to_png(&svg(), RasterSize::Dpi(192.0)).expect("Failed to render");
+        assert_eq!(dimensions(&png), (640, 256));
+    }
+
+    #[test]
+    fn it_should_fit_png_within_pixels() {
+        let size = |scale, max_pixels| RasterSize::Fit { scale, max_pixels };
+
+        let png = to_png(&svg(), size(2.0, 1_000_000)).expect("Failed to render");
+        assert_eq!(dimensions(&png), (640, 256));
+
+        let png = to_png(&svg(), size(2.0, 10_000)).expect("Failed to render");
+        let (width, height) = dimensions(&png);
+        assert!((9_000..=10_000).contains(&(width * height)));
+    }
+
+    #[test]
+    fn it_should_reject_bad_sizes() {
+        assert!(matches!(
+            to_png(&svg(), RasterSize::Scale(0.0)),
+            Err(Error::Render(_))
+        ));
+        assert_eq!(
+            to_png(&svg(), RasterSize::Scale(1000.0)),
+            Err(Error::Overflow)
+        );
+        assert_eq!(
+            to_png(&svg(), RasterSize::Scale(1e30)),
+            Err(Error::Overflow)
+        );
+    }
+}
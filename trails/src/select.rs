@@ -0,0 +1,261 @@
+use std::{collections::HashSet, fmt::Display, str::FromStr};
+
+use rand::{
+    rngs::SmallRng,
+    seq::{SliceRandom, index},
+};
+use rand_seeder::Seeder;
+
+use crate::{
+    error::Error,
+    map::{MAX_PATHS, Map, Position},
+};
+
+/// Which of the trails found are drawn
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Selection {
+    /// Every distinct route
+    #[default]
+    All,
+    /// One route from each trailhead
+    Trailhead,
+    /// One route for each trailhead and summit pair
+    Endpoints,
+    /// Only routes which don't share a cell with one already chosen
+    Disjoint,
+}
+
+impl Display for Selection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Selection::All => write!(f, "all"),
+            Selection::Trailhead => write!(f, "trailhead"),
+            Selection::Endpoints => write!(f, "endpoints"),
+            Selection::Disjoint => write!(f, "disjoint"),
+        }
+    }
+}
+
+impl FromStr for Selection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "all" => Ok(Selection::All),
+            "trailhead" => Ok(Selection::Trailhead),
+            "endpoints" => Ok(Selection::Endpoints),
+            "disjoint" => Ok(Selection::Disjoint),
+            _ => Err(format!(
+                "Unknown selection {s:?}, expected all, trailhead, endpoints or disjoint"
+            )),
+        }
+    }
+}
+
+/// Apply `selection` then keep at most `max_trails`. Trails are considered
+/// in an order shuffled by `seed`, so the same seed always picks the same
+/// trails, and the chosen ones keep the order they were found in.
+pub fn select(
+    paths: &[Vec<Position>],
+    selection: Selection,
+    max_trails: Option<usize>,
+    seed: &str,
+) -> Vec<Vec<Position>> {
+    if selection == Selection::All {
+        let max = max_trails.unwrap_or(paths.len());
+        return sample(paths.len(), max, seed)
+            .into_iter()
+            .map(|index| paths[index].clone())
+            .collect();
+    }
+
+    let mut rng: SmallRng = Seeder::from(seed).into_rng();
+    let mut candidates: Vec<usize> = (0..paths.len()).collect();
+    candidates.shuffle(&mut rng);
+
+    let mut trailheads = HashSet::new();
+    let mut pairs = HashSet::new();
+    let mut used_cells = HashSet::new();
+    let mut chosen: Vec<usize> = candidates
+        .into_iter()
+        .filter(|index| {
+            let path = &paths[*index];
+            let (Some(start), Some(end)) = (path.first(), path.last()) else {
+                return false;
+            };
+            match selection {
+                Selection::All => true,
+                Selection::Trailhead => trailheads.insert(*start),
+                Selection::Endpoints => pairs.insert((*start, *end)),
+                Selection::Disjoint => {
+                    if path.iter().any(|pos| used_cells.contains(pos)) {
+                        return false;
+                    }
+                    used_cells.extend(path.iter().copied());
+                    true
+                }
+            }
+        })
+        .collect();
+
+    if let Some(max) = max_trails {
+        chosen.truncate(max);
+    }
+    chosen.sort();
+    chosen
+        .into_iter()
+        .map(|index| paths[index].clone())
+        .collect()
+}
+
+/// Up to `max` of the indices below `count`, picked by `seed`, in order
+fn sample(count: usize, max: usize, seed: &str) -> Vec<usize> {
+    if max >= count {
+        return (0..count).collect();
+    }
+    let mut rng: SmallRng = Seeder::from(seed).into_rng();
+    let mut chosen = index::sample(&mut rng, count, max).into_vec();
+    chosen.sort_unstable();
+    chosen
+}
+
+/// Find the routes up `map` and pick from them as `select` does. Sampling
+/// from every route walks them lazily and only keeps the ones chosen, so
+/// it works on maps with more than `MAX_PATHS` routes.
+pub fn select_paths(
+    map: &Map,
+    trail_length: usize,
+    selection: Selection,
+    max_trails: Option<usize>,
+    seed: &str,
+) -> Result<Vec<Vec<Position>>, Error> {
+    let mut paths = map.paths_iter(trail_length);
+    let count = paths.size_hint().1;
+    let too_many = Error::TooManyPaths { max: MAX_PATHS };
+
+    if selection == Selection::All
+        && let (Some(max), Some(count)) = (max_trails, count)
+    {
+        if max.min(count) > MAX_PATHS {
+            return Err(too_many);
+        }
+        let mut chosen = vec![];
+        let mut skipped = 0;
+        for index in sample(count, max, seed) {
+            chosen.extend(paths.nth(index - skipped));
+            skipped = index + 1;
+        }
+        return Ok(chosen);
+    }
+
+    if count.is_none_or(|count| count > MAX_PATHS) {
+        return Err(too_many);
+    }
+    let paths: Vec<Vec<Position>> = paths.collect();
+    Ok(select(&paths, selection, max_trails, seed))
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
+
+    use crate::{
+        map::{Map, Position},
+        select::{Selection, select, select_paths},
+    };
+
+    fn paths() -> Vec<Vec<Position>> {
+        let input = "89010123
+78121874
+87430965
+96549874
+45678903
+32019012
+01329801
+10456732";
+        let mut map = Map::parse(input).expect("Failed to parse");
+        map.find_all_paths(10).expect("Too many paths");
+        map.paths
+    }
+
+    #[test]
+    fn it_should_keep_everything_by_default() {
+        let paths = paths();
+        assert_eq!(select(&paths, Selection::All, None, "seed"), paths);
+    }
+
+    #[test]
+    fn it_should_pick_one_per_trailhead() {
+        let paths = paths();
+        let trailheads: HashSet<_> = paths.iter().map(|path| path[0]).collect();
+        let chosen = select(&paths, Selection::Trailhead, None, "seed");
+
+        assert_eq!(chosen.len(), trailheads.len());
+        let chosen_heads: HashSet<_> = chosen.iter().map(|path| path[0]).collect();
+        assert_eq!(chosen_heads, trailheads);
+    }
+
+    #[test]
+    fn it_should_pick_one_per_endpoint_pair() {
+        let paths = paths();
+        let pairs: HashSet<_> = paths
+            .iter()
+            .map(|path| (path[0], path[path.len() - 1]))
+            .collect();
+        let chosen = select(&paths, Selection::Endpoints, None, "seed");
+
+        assert!(pairs.len() < paths.len());
+        assert_eq!(chosen.len(), pairs.len());
+    }
+
+    #[test]
+    fn it_should_pick_disjoint_paths() {
+        let chosen = select(&paths(), Selection::Disjoint, None, "seed");
+        let mut cells = HashSet::new();
+
+        assert!(!chosen.is_empty());
+        for path in chosen {
+            assert!(path.iter().all(|pos| cells.insert(*pos)));
+        }
+    }
+
+    #[test]
+    fn it_should_sample_from_seed() {
+        let paths = paths();
+        let first = select(&paths, Selection::All, Some(5), "seed");
+
+        assert_eq!(first.len(), 5);
+        assert_eq!(first, select(&paths, Selection::All, Some(5), "seed"));
+        assert_ne!(first, select(&paths, Selection::All, Some(5), "other"));
+        // found order is kept
+        let positions: Vec<usize> = first
+            .iter()
+            .map(|path| paths.iter().position(|p| p == path).expect("Not found"))
+            .collect();
+        assert!(positions.is_sorted());
+    }
+
+    #[test]
+    fn it_should_sample_lazily() {
+        let map = Map::parse("0123\n1234\n2345\n3456").expect("Failed to parse");
+        assert_eq!(
+            select_paths(&map, 7, Selection::All, Some(3), "seed"),
+            Ok(select(
+                &map.paths_iter(7).collect::<Vec<_>>(),
+                Selection::All,
+                Some(3),
+                "seed"
+            ))
+        );
+        assert_eq!(
+            select_paths(&map, 7, Selection::Disjoint, None, "seed").map(|paths| paths.len()),
+            Ok(1)
+        );
+    }
+
+    #[test]
+    fn it_should_parse_selection() {
+        assert_eq!("disjoint".parse::<Selection>(), Ok(Selection::Disjoint));
+        assert!("best".parse::<Selection>().is_err());
+    }
+}
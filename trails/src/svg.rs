@@ -1,19 +1,24 @@
-use std::{cmp::Ordering, fmt::Display};
+use std::fmt::Display;
 
-use crate::map::{Map, Position};
-
-#[derive(Debug)]
-enum Direction {
-    North,
-    East,
-    South,
-    West,
-}
+use crate::{
+    config::{DEFAULT_CORNER_RADIUS, Style},
+    curve::{PathStyle, Point, catmull_rom, cut_corners},
+    error::Error,
+    graph::TrailGraph,
+    map::{Map, Position},
+    order::{PathOrder, PathOrdering, Step},
+    quadtree::Leaf,
+    topology::{HEX_ROW_HEIGHT, Neighbourhood},
+};
 
 #[derive(Debug, PartialEq, Clone, Copy)]
 enum LineCommand {
     Horizontal,
     Vertical,
+    /// `distance` is horizontal and `dy` vertical
+    Diagonal {
+        dy: i16,
+    },
 }
 
 #[derive(Debug, PartialEq, Clone, Copy)]
@@ -31,11 +36,120 @@ impl Display for SvgCommand {
             LineCommand::Vertical => {
                 write!(f, "v{}", self.distance).expect("Failed to write command");
             }
+            LineCommand::Diagonal { dy } => {
+                write!(f, "l{},{}", self.distance, dy).expect("Failed to write command");
+            }
         }
         Ok(())
     }
 }
 
+impl SvgCommand {
+    fn new(dx: i16, dy: i16) -> Self {
+        let command = match (dx, dy) {
+            (_, 0) => LineCommand::Horizontal,
+            (0, _) => LineCommand::Vertical,
+            _ => LineCommand::Diagonal { dy },
+        };
+        SvgCommand {
+            command,
+            distance: if command == LineCommand::Vertical {
+                dy
+            } else {
+                dx
+            },
+        }
+    }
+
+    /// Direction of the command along each axis
+    fn step(&self) -> (i16, i16) {
+        match self.command {
+            LineCommand::Horizontal => (self.distance.signum(), 0),
+            LineCommand::Vertical => (0, self.distance.signum()),
+            LineCommand::Diagonal { dy } => (self.distance.signum(), dy.signum()),
+        }
+    }
+
+    /// Whether `next` carries on in the same line, so the two can be merged
+    fn continues(&self, next: &SvgCommand) -> bool {
+        match (self.command, next.command) {
+            (LineCommand::Diagonal { .. }, LineCommand::Diagonal { .. }) => {
+                self.step() == next.step()
+            }
+            (command, next) => command == next,
+        }
+    }
+
+    fn extend(&mut self, next: &SvgCommand) {
+        self.distance += next.distance;
+        if let (LineCommand::Diagonal { dy }, LineCommand::Diagonal { dy: next_dy }) =
+            (&mut self.command, next.command)
+        {
+            *dy += next_dy;
+        }
+    }
+
+    /// Pull the end back by `by` along each axis it moves along
+    fn shorten(&mut self, by: (i16, i16)) {
+        let (x, y) = self.step();
+        match &mut self.command {
+            LineCommand::Horizontal => self.distance -= x * by.0,
+            LineCommand::Vertical => self.distance -= y * by.1,
+            LineCommand::Diagonal { dy } => {
+                self.distance -= x * by.0;
+                *dy -= y * by.1;
+            }
+        }
+    }
+}
+
+/// Circle at a trailhead or square at a summit, by centre
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Marker {
+    Start(Point),
+    End(Point),
+}
+
+/// Line through a trail plus the markers drawn at either end of it
+#[derive(Debug, PartialEq, Clone)]
+pub struct TrailShape {
+    pub path: Vec<Point>,
+    /// Drawn before the line, where it starts
+    pub head: Option<Marker>,
+    /// Drawn after the line, where it finishes
+    pub tail: Option<Marker>,
+}
+
+/// Grid points of a line to draw, in drawing order
+struct Stroke {
+    points: Vec<Position>,
+    /// Index into `trail_colours`
+    colour: usize,
+    head: Option<Marker>,
+    tail: Option<Marker>,
+    /// Marker under each end, which the line is pulled back from
+    trim: (Option<Marker>, Option<Marker>),
+}
+
+/// Layers showing how the canvas was split into leaves and where trails
+/// started, for tuning leaf size and density
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Overlay {
+    pub leaves: Vec<Leaf>,
+    /// Start points, and whether each grew a full trail
+    pub starts: Vec<(Position, bool)>,
+}
+
+impl Overlay {
+    /// Leaves of `root`, and starts with whether each made a trail
+    pub fn new(root: &Leaf, starts: &[(Position, bool)]) -> Self {
+        Overlay {
+            leaves: root.leaves().into_iter().cloned().collect(),
+            starts: starts.to_vec(),
+        }
+    }
+}
+
 #[derive(PartialEq, Debug)]
 pub struct Svg<'a> {
     pub tile_size: usize,
@@ -45,6 +159,25 @@ pub struct Svg<'a> {
     pub width: usize,
     pub height: usize,
     pub end_radius: usize,
+    /// Colour for each trail, by path index. Falls back to `colour`
+    pub trail_colours: Vec<&'a str>,
+    pub path_style: PathStyle,
+    /// Size of rounded or chamfered corners
+    pub corner_radius: usize,
+    /// Drawing order and direction of trails, or of strokes when merging.
+    /// Empty draws them as found
+    pub order: Vec<Step>,
+    /// Draw the union of every trail, so shared segments are only drawn once
+    pub merge: bool,
+    /// Map width and height in cells
+    pub grid: (usize, usize),
+    /// Hex maps are drawn as a honeycomb, anything else as a square grid
+    pub neighbourhood: Neighbourhood,
+    /// Trails cross the edges, and the canvas is one repeat of the pattern
+    pub wrap: bool,
+    /// Debug layers, drawn as the `grid`, `leaves`, `depths` and `starts`
+    /// groups
+    pub overlay: Option<Overlay>,
 }
 
 impl<'a> Svg<'a> {
@@ -62,40 +195,220 @@ impl<'a> Svg<'a> {
             offset,
             stroke_width,
             colour,
-            width: (map_width - 1) * tile_size + (offset * 2),
-            height: (map_height - 1) * tile_size + (offset * 2),
+            width: map_width.saturating_sub(1) * tile_size + (offset * 2),
+            height: map_height.saturating_sub(1) * tile_size + (offset * 2),
             end_radius,
+            trail_colours: vec![],
+            path_style: PathStyle::default(),
+            corner_radius: DEFAULT_CORNER_RADIUS,
+            order: vec![],
+            merge: false,
+            grid: (map_width, map_height),
+            neighbourhood: Neighbourhood::default(),
+            wrap: false,
+            overlay: None,
+        }
+    }
+
+    pub fn from_style(style: &'a Style, map_width: usize, map_height: usize) -> Self {
+        Svg::new(
+            style.tile_size,
+            style.offset,
+            map_width,
+            map_height,
+            style.stroke_width,
+            &style.colour,
+            style.end_radius,
+        )
+        .with_path_style(style.path_style, style.corner_radius)
+        .with_merge(style.merge)
+        .with_debug_overlay(style.debug_overlay)
+    }
+
+    pub fn with_path_style(mut self, path_style: PathStyle, corner_radius: usize) -> Self {
+        self.path_style = path_style;
+        self.corner_radius = corner_radius;
+        self
+    }
+
+    pub fn with_trail_colours(mut self, trail_colours: Vec<&'a str>) -> Self {
+        self.trail_colours = trail_colours;
+        self
+    }
+
+    pub fn with_order(mut self, order: Vec<Step>) -> Self {
+        self.order = order;
+        self
+    }
+
+    pub fn with_merge(mut self, merge: bool) -> Self {
+        self.merge = merge;
+        self
+    }
+
+    /// Draw just the grid until leaves and starts are added with
+    /// `with_overlay`
+    pub fn with_debug_overlay(mut self, debug_overlay: bool) -> Self {
+        self.overlay = debug_overlay.then(Overlay::default);
+        self
+    }
+
+    pub fn with_overlay(mut self, overlay: Overlay) -> Self {
+        self.overlay = Some(overlay);
+        self
+    }
+
+    /// Lay the map out for `neighbourhood`, resizing the canvas to fit
+    pub fn with_neighbourhood(mut self, neighbourhood: Neighbourhood) -> Self {
+        self.neighbourhood = neighbourhood;
+        self.resize();
+        self
+    }
+
+    /// Draw a square grid map whose trails cross the edges as a seamless
+    /// tile. Cells are centred in their tiles, replacing `offset`, so the
+    /// spacing stays even across the join.
+    pub fn with_wrap(mut self, wrap: bool) -> Self {
+        self.wrap = wrap;
+        if wrap {
+            self.offset = self.tile_size / 2;
+        }
+        self.resize();
+        self
+    }
+
+    fn resize(&mut self) {
+        if self.wrap {
+            self.width = self.grid.0 * self.tile_size;
+            self.height = self.grid.1 * self.tile_size;
+            return;
+        }
+        // the bottom right cell is furthest right on a hex map too
+        let last = self.pixel(Position {
+            x: self.grid.0.saturating_sub(1),
+            y: self.grid.1.saturating_sub(1),
+        });
+        self.width = last.0 as usize + self.offset;
+        self.height = last.1 as usize + self.offset;
+    }
+
+    /// Lines which will be drawn before ordering, trails or merged strokes
+    pub fn lines(&self, map: &Map) -> Vec<Vec<Position>> {
+        match self.merge {
+            true => TrailGraph::from_map(map).strokes(),
+            false => map.paths.clone(),
+        }
+    }
+
+    /// Order for whatever this draws, to pass to `with_order`
+    pub fn path_order(&self, map: &Map, ordering: PathOrdering) -> PathOrder {
+        PathOrder::new(&self.lines(map), ordering, self.neighbourhood)
+    }
+
+    fn steps(&self, count: usize) -> Vec<Step> {
+        if self.order.is_empty() {
+            return (0..count).map(|index| (index, false)).collect();
+        }
+        self.order.clone()
+    }
+
+    /// Every line in drawing order with its markers
+    fn strokes(&self, map: &Map) -> Result<Vec<Stroke>, Error> {
+        if let Some(trail) = map.paths.iter().find(|trail| trail.len() < 2) {
+            return Err(Error::TrailTooShort { len: trail.len() });
+        }
+        let ordered = |line: &[Position], reversed: bool| match reversed {
+            true => line.iter().rev().copied().collect(),
+            false => line.to_vec(),
+        };
+
+        if !self.merge {
+            let strokes = self
+                .steps(map.paths.len())
+                .into_iter()
+                .map(|(index, reversed)| {
+                    let trail = &map.paths[index];
+                    let start = Some(Marker::Start(self.pixel(trail[0])));
+                    let end = Some(Marker::End(self.pixel(trail[trail.len() - 1])));
+                    let (head, tail) = if reversed { (end, start) } else { (start, end) };
+                    Stroke {
+                        points: ordered(trail, reversed),
+                        colour: index,
+                        head,
+                        tail,
+                        trim: (head, tail),
+                    }
+                });
+            return Ok(strokes.collect());
         }
+
+        // markers are shared too, so each is drawn by the first line to reach it
+        let graph = TrailGraph::from_map(map);
+        let lines = graph.strokes();
+        let under = |pos: Position| {
+            if !graph.is_marker(&pos) {
+                return None;
+            }
+            match graph.trailheads.contains(&pos) {
+                true => Some(Marker::Start(self.pixel(pos))),
+                false => Some(Marker::End(self.pixel(pos))),
+            }
+        };
+        let mut drawn = vec![];
+        let mut marker = |pos: Position| {
+            if drawn.contains(&pos) {
+                return None;
+            }
+            let marker = under(pos)?;
+            drawn.push(pos);
+            Some(marker)
+        };
+
+        Ok(self
+            .steps(lines.len())
+            .into_iter()
+            .map(|(index, reversed)| {
+                let points = ordered(&lines[index], reversed);
+                let (first, last) = (points[0], points[points.len() - 1]);
+                Stroke {
+                    colour: graph.path_index(first, points[1]).unwrap_or(0),
+                    head: marker(first),
+                    tail: marker(last),
+                    trim: (under(first), under(last)),
+                    points,
+                }
+            })
+            .collect())
     }
 
-    fn draw_path(&self, path: &str) -> String {
+    fn trail_colour(&self, index: usize) -> &str {
+        self.trail_colours.get(index).unwrap_or(&self.colour)
+    }
+
+    fn draw_path(&self, path: &str, colour: &str) -> String {
         format!(
             "<path d=\"{path}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\" stroke-linecap=\"square\" />",
-            self.colour, self.stroke_width
+            colour, self.stroke_width
         )
     }
 
     fn get_path(&self, points: &[Position]) -> Vec<SvgCommand> {
-        let commands = points
+        points
             .windows(2)
             .map(|slice| self.make_command(slice[0], slice[1]))
-            .collect();
-        commands
+            .collect()
     }
 
     fn make_command(&self, first: Position, second: Position) -> SvgCommand {
-        let is_horz = first.x != second.x;
-        if is_horz {
-            SvgCommand {
-                command: LineCommand::Horizontal,
-                distance: self.to_pixel(second.x) as i16 - self.to_pixel(first.x) as i16,
-            }
-        } else {
-            SvgCommand {
-                command: LineCommand::Vertical,
-                distance: self.to_pixel(second.y) as i16 - self.to_pixel(first.y) as i16,
-            }
+        if self.wrap {
+            // steps across an edge carry on off the canvas
+            let tile = self.tile_size as i16;
+            let dx = wrap_step(first.x, second.x, self.grid.0) * tile;
+            let dy = wrap_step(first.y, second.y, self.grid.1) * tile;
+            return SvgCommand::new(dx, dy);
         }
+        let (from, to) = (self.pixel(first), self.pixel(second));
+        SvgCommand::new((to.0 - from.0) as i16, (to.1 - from.1) as i16)
     }
 
     /// Combine consecutive duplicate commands
@@ -106,9 +419,9 @@ impl<'a> Svg<'a> {
         for (i, item) in path.iter().enumerate() {
             if i == 0 {
                 output.push(*item);
-            } else if item.command == output[output.len() - 1].command {
+            } else if output[output.len() - 1].continues(item) {
                 let stored_index = output.len() - 1;
-                output[stored_index].distance += item.distance;
+                output[stored_index].extend(item);
             } else {
                 output.push(*item);
             }
@@ -117,26 +430,47 @@ impl<'a> Svg<'a> {
         output
     }
 
-    fn start(&self, centre: Position) -> String {
+    /// Absolute pixel position after each command
+    fn points(&self, start: Point, path: &[SvgCommand]) -> Vec<Point> {
+        let mut current = start;
+        let mut points = vec![current];
+        for cmd in path {
+            match cmd.command {
+                LineCommand::Horizontal => current.0 += cmd.distance as f64,
+                LineCommand::Vertical => current.1 += cmd.distance as f64,
+                LineCommand::Diagonal { dy } => {
+                    current.0 += cmd.distance as f64;
+                    current.1 += dy as f64;
+                }
+            }
+            points.push(current);
+        }
+        points
+    }
+
+    fn marker(&self, marker: Marker, colour: &str) -> String {
+        match marker {
+            Marker::Start(centre) => self.start(centre, colour),
+            Marker::End(centre) => self.end(centre, colour),
+        }
+    }
+
+    fn start(&self, centre: Point, colour: &str) -> String {
         format!(
             "<circle cx=\"{}\" cy=\"{}\" stroke-width=\"{}\" fill=\"transparent\" stroke=\"{}\" r=\"{}\" />",
-            centre.x * self.tile_size + self.offset,
-            centre.y * self.tile_size + self.offset,
-            self.stroke_width,
-            self.colour,
-            self.end_radius
+            centre.0, centre.1, self.stroke_width, colour, self.end_radius
         )
     }
 
-    fn end(&self, centre: Position) -> String {
+    fn end(&self, centre: Point, colour: &str) -> String {
         format!(
             "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" stroke-width=\"{}\" fill=\"transparent\" stroke=\"{}\" />",
-            (centre.x * self.tile_size + self.offset) - self.end_radius,
-            (centre.y * self.tile_size + self.offset) - self.end_radius,
+            centre.0 - self.end_radius as f64,
+            centre.1 - self.end_radius as f64,
             self.end_radius * 2,
             self.end_radius * 2,
             self.stroke_width,
-            self.colour
+            colour
         )
     }
 
@@ -144,99 +478,383 @@ impl<'a> Svg<'a> {
         point * self.tile_size + self.offset
     }
 
-    fn get_direction(&self, first: Position, second: Position) -> Direction {
-        match first.x.cmp(&second.x) {
-            Ordering::Equal => {
-                // if x's are equal, moving vert
-                if first.y < second.y {
-                    Direction::South
-                } else {
-                    Direction::North
-                }
-            }
-            _ => {
-                if first.x < second.x {
-                    Direction::West
-                } else {
-                    Direction::East
-                }
+    /// How far a line ending with `cmd` is pulled back from the centre of
+    /// `marker` along each axis, so it stops at the edge of a circle or
+    /// square. Diagonals meet a square at its side or corner.
+    fn pull_back(&self, marker: Option<Marker>, cmd: &SvgCommand) -> (i16, i16) {
+        let (dx, dy) = match cmd.command {
+            LineCommand::Horizontal => (cmd.distance as f64, 0.0),
+            LineCommand::Vertical => (0.0, cmd.distance as f64),
+            LineCommand::Diagonal { dy } => (cmd.distance as f64, dy as f64),
+        };
+        let scale = match marker {
+            None => return (0, 0),
+            Some(Marker::Start(_)) => (dx * dx + dy * dy).sqrt(),
+            Some(Marker::End(_)) => dx.abs().max(dy.abs()),
+        };
+        let radius = self.end_radius as f64 / scale;
+        (
+            (dx.abs() * radius).round() as i16,
+            (dy.abs() * radius).round() as i16,
+        )
+    }
+
+    /// Pixel position of a grid point. Hex maps use axial coordinates, with
+    /// each row shifted half a tile right of the one above. Points are
+    /// rounded so relative commands never drift.
+    fn pixel(&self, pos: Position) -> Point {
+        match self.neighbourhood {
+            Neighbourhood::Hex => {
+                let tile = self.tile_size as f64;
+                let x = tile * (pos.x as f64 + pos.y as f64 / 2.0);
+                let y = tile * HEX_ROW_HEIGHT * pos.y as f64;
+                (
+                    x.round() + self.offset as f64,
+                    y.round() + self.offset as f64,
+                )
             }
+            _ => (self.to_pixel(pos.x) as f64, self.to_pixel(pos.y) as f64),
         }
     }
 
-    pub fn draw(&self, map: &Map) -> String {
-        let mut output = format!(
-            "<svg viewBox=\"0 0 {} {}\" xmlns=\"http://www.w3.org/2000/svg\">",
-            self.width, self.height
-        );
+    /// Pixel position of a point in grid units, laid out as `pixel`, for
+    /// the corners between cells
+    fn corner(&self, x: f64, y: f64) -> Point {
+        let tile = self.tile_size as f64;
+        let (x, y) = match self.neighbourhood {
+            Neighbourhood::Hex => (tile * (x + y / 2.0), tile * HEX_ROW_HEIGHT * y),
+            _ => (tile * x, tile * y),
+        };
+        let round = |value: f64| (value * 10.0).round() / 10.0;
+        (round(x + self.offset as f64), round(y + self.offset as f64))
+    }
 
-        map.paths.iter().for_each(|trail| {
-            let start_dir = self.get_direction(trail[0], trail[1]);
-            let end_dir = self.get_direction(trail[trail.len() - 2], trail[trail.len() - 1]);
+    /// Line between two points in grid units
+    fn segment(&self, from: (f64, f64), to: (f64, f64)) -> String {
+        let from = self.corner(from.0, from.1);
+        let to = self.corner(to.0, to.1);
+        format!("M{},{} L{},{} ", from.0, from.1, to.0, to.1)
+    }
 
-            let mut path_cmds = self.get_path(trail);
+    /// Cell edges, drawn under the trails
+    fn draw_grid(&self) -> String {
+        let (columns, rows) = self.grid;
+        let (right, bottom) = (columns as f64 - 0.5, rows as f64 - 0.5);
+        let mut data = String::new();
+        for column in 0..=columns {
+            let x = column as f64 - 0.5;
+            data += &self.segment((x, -0.5), (x, bottom));
+        }
+        for row in 0..=rows {
+            let y = row as f64 - 0.5;
+            data += &self.segment((-0.5, y), (right, y));
+        }
+        format!(
+            "<g id=\"grid\" stroke=\"#d3d3d3\" stroke-width=\"1\" fill=\"none\"><path d=\"{}\" /></g>",
+            data.trim_end()
+        )
+    }
 
-            // adjust end of trail for rect
-            // -2: len - 1 for last item, and there should be one fewer edges than nodes
-            let last_index = path_cmds.len() - 1;
-            match end_dir {
-                Direction::North => path_cmds[last_index].distance += self.end_radius as i16,
-                Direction::South => path_cmds[last_index].distance -= self.end_radius as i16,
-                Direction::East => path_cmds[last_index].distance += self.end_radius as i16,
-                Direction::West => path_cmds[last_index].distance -= self.end_radius as i16,
+    /// Outline of each leaf, its depth at the centre of its bounds, and the
+    /// start points, marking those which didn't grow a trail with a cross
+    fn draw_overlay(&self, overlay: &Overlay) -> String {
+        let mut outlines = String::new();
+        let mut depths = String::new();
+        for leaf in &overlay.leaves {
+            let (left, top) = (leaf.x as f64 - 0.5, leaf.y as f64 - 0.5);
+            let (right, bottom) = (left + leaf.width as f64, top + leaf.height as f64);
+            let data = match &leaf.region {
+                None => [
+                    ((left, top), (right, top)),
+                    ((right, top), (right, bottom)),
+                    ((right, bottom), (left, bottom)),
+                    ((left, bottom), (left, top)),
+                ]
+                .iter()
+                .map(|(from, to)| self.segment(*from, *to))
+                .collect(),
+                Some(region) => {
+                    // edges between cells in the leaf and cells outside it
+                    let inside = |x: isize, y: isize| {
+                        x >= 0
+                            && y >= 0
+                            && (x as usize) < leaf.width
+                            && (y as usize) < leaf.height
+                            && region[y as usize * leaf.width + x as usize]
+                    };
+                    let mut data = String::new();
+                    for index in (0..region.len()).filter(|index| region[*index]) {
+                        let (x, y) = ((index % leaf.width) as isize, (index / leaf.width) as isize);
+                        let (cell_left, cell_top) = (left + x as f64, top + y as f64);
+                        let (cell_right, cell_bottom) = (cell_left + 1.0, cell_top + 1.0);
+                        for (outside, from, to) in [
+                            (
+                                !inside(x, y - 1),
+                                (cell_left, cell_top),
+                                (cell_right, cell_top),
+                            ),
+                            (
+                                !inside(x + 1, y),
+                                (cell_right, cell_top),
+                                (cell_right, cell_bottom),
+                            ),
+                            (
+                                !inside(x, y + 1),
+                                (cell_left, cell_bottom),
+                                (cell_right, cell_bottom),
+                            ),
+                            (
+                                !inside(x - 1, y),
+                                (cell_left, cell_top),
+                                (cell_left, cell_bottom),
+                            ),
+                        ] {
+                            if outside {
+                                data += &self.segment(from, to);
+                            }
+                        }
+                    }
+                    data
+                }
             };
+            outlines += &format!("<path d=\"{}\" />", data.trim_end());
 
-            let mut start_x = self.to_pixel(trail[0].x);
-            let mut start_y = self.to_pixel(trail[0].y);
+            let centre = self.corner((left + right) / 2.0, (top + bottom) / 2.0);
+            depths += &format!(
+                "<text x=\"{}\" y=\"{}\">{}</text>",
+                centre.0, centre.1, leaf.depth
+            );
+        }
 
-            match start_dir {
-                Direction::North => {
-                    start_y -= self.end_radius;
-                    path_cmds[0].distance += self.end_radius as i16;
-                }
-                Direction::South => {
-                    start_y += self.end_radius;
-                    path_cmds[0].distance -= self.end_radius as i16;
+        let size = (self.tile_size / 6).max(1);
+        let mut starts = String::new();
+        for (pos, grown) in &overlay.starts {
+            let centre = self.pixel(*pos);
+            starts += &match grown {
+                true => format!(
+                    "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"#2e8b57\" />",
+                    centre.0, centre.1, size
+                ),
+                false => format!(
+                    "<path d=\"M{},{} l{},{} m0,{} l{},{}\" stroke=\"#dc143c\" />",
+                    centre.0 - size as f64,
+                    centre.1 - size as f64,
+                    size * 2,
+                    size * 2,
+                    -(size as isize * 2),
+                    -(size as isize * 2),
+                    size * 2
+                ),
+            };
+        }
+
+        format!(
+            "<g id=\"leaves\" stroke=\"#1e90ff\" stroke-width=\"{}\" fill=\"none\">{}</g>\
+             <g id=\"depths\" fill=\"#1e90ff\" font-family=\"sans-serif\" font-size=\"{}\" text-anchor=\"middle\" dominant-baseline=\"central\">{}</g>\
+             <g id=\"starts\" stroke-width=\"{}\">{}</g>",
+            self.stroke_width,
+            outlines,
+            self.tile_size / 2,
+            depths,
+            self.stroke_width,
+            starts
+        )
+    }
+
+    fn check_size(&self) -> Result<(), Error> {
+        // path commands use relative distances stored as i16
+        if i16::try_from(self.width).is_err() || i16::try_from(self.height).is_err() {
+            return Err(Error::Overflow);
+        }
+        Ok(())
+    }
+
+    /// Start of the line and the commands to draw it, pulled back from the
+    /// centre of the start and end markers so lines don't overlap them.
+    /// `trim` has the marker under each end, if any.
+    fn trail_commands(
+        &self,
+        trail: &[Position],
+        trim: (Option<Marker>, Option<Marker>),
+    ) -> Result<(Point, Vec<SvgCommand>), Error> {
+        if trail.len() < 2 {
+            return Err(Error::TrailTooShort { len: trail.len() });
+        }
+
+        let mut path_cmds = self.get_path(trail);
+
+        // adjust end of trail for rect
+        let last_index = path_cmds.len() - 1;
+        let end_radius = self.pull_back(trim.1, &path_cmds[last_index]);
+        path_cmds[last_index].shorten(end_radius);
+
+        // move the start forward, and shorten the first command to match
+        let end_radius = self.pull_back(trim.0, &path_cmds[0]);
+        let (x, y) = path_cmds[0].step();
+        let start = self.pixel(trail[0]);
+        let start = (
+            start.0 + (x * end_radius.0) as f64,
+            start.1 + (y * end_radius.1) as f64,
+        );
+        path_cmds[0].shorten(end_radius);
+
+        Ok((start, path_cmds))
+    }
+
+    /// Cut a line at every edge of the canvas it crosses and move each piece
+    /// back onto the canvas, so a wrapped trail leaves one side and comes
+    /// back in on the other
+    fn wrap_pieces(&self, points: &[Point]) -> Vec<Vec<Point>> {
+        let (width, height) = (self.width as f64, self.height as f64);
+        let mut pieces: Vec<Vec<Point>> = vec![];
+        let mut current = None;
+
+        for pair in points.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            let at = |t: f64| (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+
+            // fractions along the segment where it crosses an edge
+            let mut cuts = vec![0.0, 1.0];
+            for (from, to, size) in [(a.0, b.0, width), (a.1, b.1, height)] {
+                let mut edge = (from.min(to) / size).floor() * size + size;
+                while edge < from.max(to) {
+                    cuts.push((edge - from) / (to - from));
+                    edge += size;
                 }
-                Direction::East => {
-                    start_x -= self.end_radius;
-                    path_cmds[0].distance += self.end_radius as i16;
+            }
+            cuts.sort_by(f64::total_cmp);
+            cuts.dedup();
+
+            for cut in cuts.windows(2) {
+                let middle = at((cut[0] + cut[1]) / 2.0);
+                let repeat = ((middle.0 / width).floor(), (middle.1 / height).floor());
+                let shift = |p: Point| (p.0 - repeat.0 * width, p.1 - repeat.1 * height);
+                if current != Some(repeat) {
+                    current = Some(repeat);
+                    pieces.push(vec![shift(at(cut[0]))]);
                 }
-                Direction::West => {
-                    start_x += self.end_radius;
-                    path_cmds[0].distance -= self.end_radius as i16;
+                if let Some(piece) = pieces.last_mut() {
+                    piece.push(shift(at(cut[1])));
                 }
-            };
+            }
+        }
+        pieces
+    }
 
-            // add start circle
-            output += &self.start(Position {
-                x: trail[0].x,
-                y: trail[0].y,
-            });
+    /// Start and commands of each line drawn for a stroke. Only wrapped
+    /// strokes are split into more than one.
+    fn pieces(&self, stroke: &Stroke) -> Result<Vec<(Point, Vec<SvgCommand>)>, Error> {
+        let (start, path_cmds) = self.trail_commands(&stroke.points, stroke.trim)?;
+        if !self.wrap {
+            return Ok(vec![(start, path_cmds)]);
+        }
 
-            // add end rect
-            output += &self.end(trail[trail.len() - 1]);
+        Ok(self
+            .wrap_pieces(&self.points(start, &path_cmds))
+            .into_iter()
+            .map(|piece| {
+                let cmds = piece.windows(2).map(|pair| {
+                    SvgCommand::new(
+                        (pair[1].0 - pair[0].0) as i16,
+                        (pair[1].1 - pair[0].1) as i16,
+                    )
+                });
+                (piece[0], cmds.collect())
+            })
+            .collect())
+    }
 
-            // add start move
-            let mut merged = format!("M{},{}", start_x, start_y);
+    /// Geometry of every trail in pixels and drawing order, shared with the
+    /// plotter output
+    pub fn shapes(&self, map: &Map) -> Result<Vec<TrailShape>, Error> {
+        self.check_size()?;
 
-            // squash cmds
-            for cmd in self.merge_commands(path_cmds) {
-                merged += &format!("{}", cmd);
+        let mut shapes = vec![];
+        for stroke in self.strokes(map)? {
+            let pieces = self.pieces(&stroke)?;
+            let last = pieces.len() - 1;
+            for (index, (start, path_cmds)) in pieces.into_iter().enumerate() {
+                shapes.push(TrailShape {
+                    path: self.points(start, &self.merge_commands(path_cmds)),
+                    head: stroke.head.filter(|_| index == 0),
+                    tail: stroke.tail.filter(|_| index == last),
+                });
             }
-            output += &self.draw_path(&merged).to_string();
-        });
+        }
+        Ok(shapes)
+    }
+
+    pub fn draw(&self, map: &Map) -> Result<String, Error> {
+        self.check_size()?;
+
+        let mut output = format!(
+            "<svg viewBox=\"0 0 {} {}\" xmlns=\"http://www.w3.org/2000/svg\">",
+            self.width, self.height
+        );
+        if self.overlay.is_some() {
+            output += &self.draw_grid();
+        }
+
+        for stroke in self.strokes(map)? {
+            // markers in the order a plotter reaches them
+            let colour = self.trail_colour(stroke.colour);
+            for marker in [stroke.head, stroke.tail].into_iter().flatten() {
+                output += &self.marker(marker, colour);
+            }
+
+            for (start, path_cmds) in self.pieces(&stroke)? {
+                let data = match self.path_style {
+                    PathStyle::Square => {
+                        // add start move
+                        let mut merged = format!("M{},{}", start.0, start.1);
+
+                        // squash cmds
+                        for cmd in self.merge_commands(path_cmds) {
+                            merged += &format!("{}", cmd);
+                        }
+                        merged
+                    }
+                    PathStyle::Rounded | PathStyle::Chamfer => {
+                        let corners = self.points(start, &self.merge_commands(path_cmds));
+                        cut_corners(
+                            &corners,
+                            self.corner_radius as f64,
+                            self.path_style == PathStyle::Rounded,
+                        )
+                    }
+                    PathStyle::Smooth => catmull_rom(&self.points(start, &path_cmds)),
+                };
+                output += &self.draw_path(&data, colour).to_string();
+            }
+        }
+        if let Some(overlay) = &self.overlay {
+            output += &self.draw_overlay(overlay);
+        }
         output += "</svg>";
 
-        output
+        Ok(output)
+    }
+}
+
+/// Grid step from `from` to `to` on a wrapped axis of `size` cells, going
+/// the short way round
+fn wrap_step(from: usize, to: usize, size: usize) -> i16 {
+    match (to + size - from) % size {
+        0 => 0,
+        1 => 1,
+        _ => -1,
     }
 }
 
 #[cfg(test)]
 mod test {
     use crate::{
+        curve::PathStyle,
+        error::Error,
         map::{Map, Position},
-        svg::{LineCommand, Svg, SvgCommand},
+        quadtree::Leaf,
+        svg::{LineCommand, Overlay, Svg, SvgCommand},
+        topology::Neighbourhood,
     };
 
     #[test]
@@ -250,7 +868,16 @@ mod test {
                 colour: "black",
                 width: 260,
                 height: 260,
-                end_radius: 3
+                end_radius: 3,
+                trail_colours: vec![],
+                path_style: PathStyle::Square,
+                corner_radius: 8,
+                order: vec![],
+                merge: false,
+                grid: (16, 16),
+                neighbourhood: Neighbourhood::VonNeumann,
+                wrap: false,
+                overlay: None,
             }
         )
     }
@@ -267,11 +894,11 @@ mod test {
 7433376641
 6596526545
 6010449363";
-        let mut map = Map::parse(input);
-        map.find_all_paths();
+        let mut map = Map::parse(input).expect("Failed to parse");
+        map.find_all_paths(10).expect("Too many paths");
 
         let svg = Svg::new(64, 32, 10, 10, 2, "black", 10);
-        let output = svg.draw(&map);
+        let output = svg.draw(&map).expect("Failed to draw");
 
         assert_eq!(
             output,
@@ -279,6 +906,183 @@ mod test {
         );
     }
 
+    #[test]
+    fn it_should_draw_rectangular_map() {
+        let input = "01234
+98765";
+        let mut map = Map::parse(input).expect("Failed to parse");
+        map.find_all_paths(10).expect("Too many paths");
+
+        let svg = Svg::new(64, 32, map.width, map.height, 2, "black", 10);
+        assert_eq!((svg.width, svg.height), (320, 128));
+
+        let output = svg.draw(&map).expect("Failed to draw");
+        assert_eq!(
+            output,
+            "<svg viewBox=\"0 0 320 128\" xmlns=\"http://www.w3.org/2000/svg\"><circle cx=\"32\" cy=\"32\" stroke-width=\"2\" fill=\"transparent\" stroke=\"black\" r=\"10\" /><rect x=\"22\" y=\"86\" width=\"20\" height=\"20\" stroke-width=\"2\" fill=\"transparent\" stroke=\"black\" /><path d=\"M42,32h246v64h-246\" fill=\"none\" stroke=\"black\" stroke-width=\"2\" stroke-linecap=\"square\" /></svg>"
+        );
+    }
+
+    #[test]
+    fn it_should_draw_path_styles() {
+        let mut map = Map::parse("01234\n98765").expect("Failed to parse");
+        map.find_all_paths(10).expect("Too many paths");
+        let path = |style: PathStyle| {
+            let output = Svg::new(64, 32, map.width, map.height, 2, "black", 10)
+                .with_path_style(style, 8)
+                .draw(&map)
+                .expect("Failed to draw");
+            let start = output.find(" d=\"").expect("No path") + 4;
+            let end = output[start..].find('"').expect("No path end") + start;
+            output[start..end].to_string()
+        };
+
+        assert_eq!(path(PathStyle::Square), "M42,32h246v64h-246");
+        assert_eq!(
+            path(PathStyle::Rounded),
+            "M42,32L280,32A8,8 0 0 1 288,40L288,88A8,8 0 0 1 280,96L42,96"
+        );
+        assert_eq!(
+            path(PathStyle::Chamfer),
+            "M42,32L280,32L288,40L288,88L280,96L42,96"
+        );
+        let smooth = path(PathStyle::Smooth);
+        assert!(smooth.starts_with("M42,32C"));
+        assert!(smooth.ends_with(" 42,96"));
+        assert_eq!(smooth.matches('C').count(), 9);
+    }
+
+    #[test]
+    fn it_should_draw_trail_colours() {
+        let mut map = Map::parse("01234\n98765").expect("Failed to parse");
+        map.find_all_paths(10).expect("Too many paths");
+
+        let svg = Svg::new(64, 32, map.width, map.height, 2, "black", 10)
+            .with_trail_colours(vec!["#ff0000"]);
+        let output = svg.draw(&map).expect("Failed to draw");
+
+        assert_eq!(output.matches("stroke=\"#ff0000\"").count(), 3);
+        assert!(!output.contains("black"));
+    }
+
+    #[test]
+    fn it_should_draw_reversed_trails() {
+        let mut map = Map::parse("01234\n98765").expect("Failed to parse");
+        map.find_all_paths(10).expect("Too many paths");
+
+        let output = Svg::new(64, 32, map.width, map.height, 2, "black", 10)
+            .with_order(vec![(0, true)])
+            .draw(&map)
+            .expect("Failed to draw");
+
+        // line runs from the end marker, markers stay where they were
+        assert!(output.contains("d=\"M42,96h246v-64h-246\""));
+        assert!(output.find("<rect") < output.find("<circle"));
+        assert!(output.contains("<circle cx=\"32\" cy=\"32\""));
+    }
+
+    #[test]
+    fn it_should_merge_shared_segments() {
+        let input = "890
+          781
+          874
+          965
+          456
+          320
+          013
+          104";
+        let mut map = Map::parse(input).expect("Failed to parse");
+        map.find_all_paths(10).expect("Too many paths");
+        let count = |svg: &str| {
+            (
+                svg.matches("<path").count(),
+                svg.matches("<circle").count(),
+                svg.matches("<rect").count(),
+            )
+        };
+
+        let svg = Svg::new(64, 32, map.width, map.height, 2, "black", 10);
+        assert_eq!(count(&svg.draw(&map).expect("Failed to draw")), (4, 4, 4));
+
+        // two trailheads and two summits joined at two forks
+        let merged = svg.with_merge(true).draw(&map).expect("Failed to draw");
+        assert_eq!(count(&merged), (3, 2, 2));
+        // the line from the fork at (1, 6) to its trailhead only stops short at the marker
+        assert!(merged.contains("d=\"M96,416h-54\""));
+    }
+
+    #[test]
+    fn it_should_draw_diagonal_trails() {
+        let map = Map::parse("0999\n9199\n9929\n9993").expect("Failed to parse");
+        let mut map = map.with_neighbourhood(Neighbourhood::Moore);
+        map.find_all_paths(4).expect("Too many paths");
+        assert_eq!(map.paths.len(), 1);
+
+        let svg = Svg::new(64, 32, 4, 4, 2, "black", 10);
+        let output = svg.draw(&map).expect("Failed to draw");
+        // pulled back to the edge of the circle and the corner of the square
+        assert!(output.contains("d=\"M39,39l175,175\""));
+        assert!(output.contains("<rect x=\"214\" y=\"214\""));
+    }
+
+    #[test]
+    fn it_should_draw_hex_trails() {
+        let map = Map::parse("01\n29").expect("Failed to parse");
+        let mut map = map.with_neighbourhood(Neighbourhood::Hex);
+        map.find_all_paths(3).expect("Too many paths");
+        assert_eq!(map.paths.len(), 1);
+
+        let svg = Svg::new(32, 16, 2, 2, 2, "black", 10).with_neighbourhood(Neighbourhood::Hex);
+        assert_eq!((svg.width, svg.height), (80, 60));
+        let output = svg.draw(&map).expect("Failed to draw");
+        assert!(output.contains("d=\"M26,16h22l-10,18\""));
+    }
+
+    #[test]
+    fn it_should_draw_overlay() {
+        let map = Map::parse("01\n23").expect("Failed to parse");
+        let mut root = Leaf::new(0, 0, 2, 2, 1, 0);
+        let mut region = Leaf::new(0, 0, 2, 2, 1, 1);
+        region.region = Some(vec![true, false, false, false]);
+        root.children = vec![region];
+        let starts = [
+            (Position { x: 0, y: 0 }, true),
+            (Position { x: 1, y: 1 }, false),
+        ];
+
+        let svg = Svg::new(12, 6, 2, 2, 2, "black", 3);
+        assert!(!svg.draw(&map).expect("Failed to draw").contains("<g"));
+
+        let svg = svg.with_overlay(Overlay::new(&root, &starts));
+        let output = svg.draw(&map).expect("Failed to draw");
+        for group in ["grid", "leaves", "depths", "starts"] {
+            assert!(output.contains(&format!("<g id=\"{group}\"")));
+        }
+        assert!(output.contains("<path d=\"M0,0 L0,24 M12,0 L12,24 M24,0 L24,24 M0,0 L24,0"));
+        // region leaves are outlined around their cells
+        assert!(output.contains("<path d=\"M0,0 L12,0 M12,0 L12,12 M0,12 L12,12 M0,0 L0,12\" />"));
+        assert!(output.contains("<text x=\"12\" y=\"12\">1</text>"));
+        assert!(output.contains("<circle cx=\"6\" cy=\"6\" r=\"2\" fill=\"#2e8b57\" />"));
+        assert!(output.contains("<path d=\"M16,16 l4,4 m0,-4 l-4,4\" stroke=\"#dc143c\" />"));
+    }
+
+    #[test]
+    fn it_should_reject_short_trails() {
+        let mut map = Map::parse("01\n23").expect("Failed to parse");
+        map.paths = vec![vec![Position { x: 0, y: 0 }]];
+
+        let svg = Svg::new(64, 32, map.width, map.height, 2, "black", 10);
+        assert_eq!(svg.draw(&map), Err(Error::TrailTooShort { len: 1 }));
+    }
+
+    #[test]
+    fn it_should_reject_oversized_canvas() {
+        let map = Map::parse("01\n23").expect("Failed to parse");
+
+        let svg = Svg::new(64, 32, 1000, 2, 2, "black", 10);
+        assert_eq!(svg.draw(&map), Err(Error::Overflow));
+    }
+
     #[test]
     fn it_should_create_path_commands() {
         let trail = vec![
@@ -0,0 +1,167 @@
+use std::{fmt::Display, str::FromStr};
+
+use crate::map::Position;
+
+/// Which cells a trail can step to next
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Neighbourhood {
+    /// Up, down, left and right
+    #[default]
+    VonNeumann,
+    /// Diagonals as well
+    Moore,
+    /// Six neighbours, with positions as axial coordinates. A rectangle of
+    /// cells is a parallelogram of hexes, so maps and quadtree leaves keep
+    /// their rectangular storage.
+    Hex,
+}
+
+impl Display for Neighbourhood {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Neighbourhood::VonNeumann => write!(f, "von-neumann"),
+            Neighbourhood::Moore => write!(f, "moore"),
+            Neighbourhood::Hex => write!(f, "hex"),
+        }
+    }
+}
+
+impl FromStr for Neighbourhood {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "von-neumann" => Ok(Neighbourhood::VonNeumann),
+            "moore" => Ok(Neighbourhood::Moore),
+            "hex" => Ok(Neighbourhood::Hex),
+            _ => Err(format!(
+                "Unknown neighbourhood {s:?}, expected von-neumann, moore or hex"
+            )),
+        }
+    }
+}
+
+/// Up, down, right, left
+const CARDINAL: [(isize, isize); 4] = [(0, -1), (0, 1), (1, 0), (-1, 0)];
+/// Clockwise from up and right
+const DIAGONAL: [(isize, isize); 4] = [(1, -1), (1, 1), (-1, 1), (-1, -1)];
+/// The other two axial directions, up and right then down and left
+const HEX: [(isize, isize); 2] = [(1, -1), (-1, 1)];
+
+/// Distance between rows of hexes, in tiles
+pub const HEX_ROW_HEIGHT: f64 = 0.866_025_403_784_438_6;
+
+impl Neighbourhood {
+    fn offsets(&self) -> impl Iterator<Item = &(isize, isize)> {
+        let diagonals = match self {
+            Neighbourhood::VonNeumann => &DIAGONAL[..0],
+            Neighbourhood::Moore => &DIAGONAL[..],
+            Neighbourhood::Hex => &HEX[..],
+        };
+        CARDINAL.iter().chain(diagonals)
+    }
+
+    /// Cells next to `pos` on a `width` by `height` grid, cardinal
+    /// directions first. Only the axes a step moves along are bounds checked.
+    /// With `wrap` steps off one edge come back on the opposite one.
+    pub fn neighbours(
+        &self,
+        pos: &Position,
+        width: usize,
+        height: usize,
+        wrap: bool,
+    ) -> Vec<Position> {
+        let mut neighbours = vec![];
+        for (dx, dy) in self.offsets() {
+            let neighbour = match wrap {
+                true => Some(Position {
+                    x: wrapped(pos.x, *dx, width),
+                    y: wrapped(pos.y, *dy, height),
+                }),
+                false => {
+                    let x = pos.x.checked_add_signed(*dx);
+                    let y = pos.y.checked_add_signed(*dy);
+                    x.filter(|x| *dx == 0 || *x < width)
+                        .zip(y.filter(|y| *dy == 0 || *y < height))
+                        .map(|(x, y)| Position { x, y })
+                }
+            };
+            // tiny wrapped maps reach the same cell more than one way
+            if let Some(neighbour) = neighbour
+                && neighbour != *pos
+                && !neighbours.contains(&neighbour)
+            {
+                neighbours.push(neighbour);
+            }
+        }
+        neighbours
+    }
+}
+
+fn wrapped(val: usize, step: isize, size: usize) -> usize {
+    (val as isize + step).rem_euclid(size as isize) as usize
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{map::Position, topology::Neighbourhood};
+
+    #[test]
+    fn it_should_find_diagonal_neighbours() {
+        let pos = Position { x: 0, y: 1 };
+        assert_eq!(
+            Neighbourhood::Moore.neighbours(&pos, 3, 3, false),
+            vec![
+                Position { x: 0, y: 0 },
+                Position { x: 0, y: 2 },
+                Position { x: 1, y: 1 },
+                Position { x: 1, y: 0 },
+                Position { x: 1, y: 2 },
+            ]
+        );
+        assert_eq!(
+            Neighbourhood::VonNeumann
+                .neighbours(&pos, 3, 3, false)
+                .len(),
+            3
+        );
+        assert_eq!("moore".parse(), Ok(Neighbourhood::Moore));
+        assert!("triangle".parse::<Neighbourhood>().is_err());
+    }
+
+    #[test]
+    fn it_should_find_hex_neighbours() {
+        let pos = Position { x: 1, y: 1 };
+        assert_eq!(
+            Neighbourhood::Hex.neighbours(&pos, 3, 3, false),
+            vec![
+                Position { x: 1, y: 0 },
+                Position { x: 1, y: 2 },
+                Position { x: 2, y: 1 },
+                Position { x: 0, y: 1 },
+                Position { x: 2, y: 0 },
+                Position { x: 0, y: 2 },
+            ]
+        );
+        assert_eq!("hex".parse(), Ok(Neighbourhood::Hex));
+    }
+
+    #[test]
+    fn it_should_wrap_around_edges() {
+        let pos = Position { x: 0, y: 0 };
+        assert_eq!(
+            Neighbourhood::VonNeumann.neighbours(&pos, 3, 4, true),
+            vec![
+                Position { x: 0, y: 3 },
+                Position { x: 0, y: 1 },
+                Position { x: 1, y: 0 },
+                Position { x: 2, y: 0 },
+            ]
+        );
+        // both ways round a two cell row lead to the same place
+        assert_eq!(
+            Neighbourhood::VonNeumann.neighbours(&pos, 2, 1, true),
+            vec![Position { x: 1, y: 0 }]
+        );
+    }
+}
@@ -1,10 +1,28 @@
-use clap::{Parser, Subcommand};
+use clap::{Args as ClapArgs, Parser, Subcommand, ValueEnum, builder::RangedU64ValueParser};
 use std::{
-    fs::{read_to_string, write},
-    io,
+    error::Error,
+    fs::{read, read_to_string, write},
+    process::ExitCode,
 };
 
-use trails::{map::Map, svg::Svg};
+use trails::{
+    alphabet::Alphabet,
+    config::{DEFAULT_TRAIL_LENGTH, GenerationConfig, Style, validate_trails},
+    curve::PathStyle,
+    fill::FillStrategy,
+    map::Map,
+    mask::Shape,
+    order::{PathOrder, PathOrdering},
+    palette::{ColourStrategy, Palette},
+    partition::Partition,
+    placement::{Greyscale, Placement},
+    plot::{GCode, Hpgl, strokes},
+    quadtree::SplitRules,
+    raster::{RasterSize, to_png},
+    select::{Selection, select_paths},
+    svg::Svg,
+    topology::Neighbourhood,
+};
 
 #[derive(Parser)]
 #[command(version, about, long_about = None)]
@@ -16,56 +34,479 @@ struct Args {
 #[derive(Subcommand)]
 enum Commands {
     /// Create new trail image
-    Generate(GenerateArgs),
+    Generate(Box<GenerateArgs>),
     /// Read input map from file
-    FromFile(FromFileArgs),
+    FromFile(Box<FromFileArgs>),
+    /// Print the score and rating of every trailhead in a map file
+    Stats(StatsArgs),
+}
+
+#[derive(ClapArgs)]
+struct StyleArgs {
+    #[arg(long, default_value_t = Style::default().tile_size)]
+    tile_size: usize,
+    #[arg(long, default_value_t = Style::default().offset)]
+    offset: usize,
+    #[arg(long, default_value_t = Style::default().stroke_width)]
+    stroke_width: usize,
+    #[arg(long, default_value_t = Style::default().colour)]
+    colour: String,
+    #[arg(long, default_value_t = Style::default().end_radius)]
+    end_radius: usize,
+    /// Built in palette (mono, ocean, sunset, forest, neon) or comma
+    /// separated colours. Overrides colour
+    #[arg(short, long)]
+    palette: Option<Palette>,
+    /// How trails pick a palette colour: trailhead, index, length, leaf or random
+    #[arg(long, default_value_t = ColourStrategy::default())]
+    colour_strategy: ColourStrategy,
+    /// Corner style: square, rounded, smooth or chamfer
+    #[arg(long, default_value_t = PathStyle::default())]
+    path_style: PathStyle,
+    /// Size of rounded or chamfered corners
+    #[arg(long, default_value_t = Style::default().corner_radius)]
+    corner_radius: usize,
+    /// Trail order: scan, travel (shortest pen up travel) or reversible
+    /// (travel, also drawing trails backwards)
+    #[arg(long, default_value_t = PathOrdering::default())]
+    ordering: PathOrdering,
+    /// Draw segments shared by several trails once
+    #[arg(long)]
+    merge: bool,
+    /// Draw the grid, leaves and start points, crossing out starts which
+    /// didn't grow a trail
+    #[arg(long)]
+    debug_overlay: bool,
+}
+
+impl From<&StyleArgs> for Style {
+    fn from(args: &StyleArgs) -> Self {
+        Style {
+            tile_size: args.tile_size,
+            offset: args.offset,
+            stroke_width: args.stroke_width,
+            colour: args.colour.clone(),
+            end_radius: args.end_radius,
+            palette: args.palette.clone(),
+            colour_strategy: args.colour_strategy,
+            path_style: args.path_style,
+            corner_radius: args.corner_radius,
+            ordering: args.ordering,
+            merge: args.merge,
+            debug_overlay: args.debug_overlay,
+        }
+    }
+}
+
+#[derive(ClapArgs)]
+struct SelectionArgs {
+    /// Trails to draw: all, trailhead (one per trailhead), endpoints (one
+    /// per trailhead and summit pair) or disjoint (no shared cells)
+    #[arg(long, default_value_t = Selection::default())]
+    selection: Selection,
+    /// Most trails to draw, picked at random from the seed
+    #[arg(long)]
+    max_trails: Option<usize>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Svg,
+    Png,
+    Gcode,
+    Hpgl,
+}
+
+#[derive(ClapArgs)]
+struct OutputArgs {
+    #[arg(short, long, value_enum, default_value_t = Format::Svg)]
+    format: Format,
+    /// PNG pixels per SVG unit, from 0.1 to 4
+    #[arg(long, default_value_t = 1.0)]
+    scale: f32,
+    /// PNG resolution, from 10 to 384. Overrides scale
+    #[arg(long)]
+    dpi: Option<f32>,
+    /// PNG width in pixels, the height keeps the aspect ratio. Overrides scale
+    #[arg(long, conflicts_with = "dpi", value_parser = clap::value_parser!(u32).range(1..=10_000))]
+    png_width: Option<u32>,
+    /// Plotter paper width in mm, the drawing keeps its aspect ratio
+    #[arg(long, default_value_t = GCode::default().width_mm)]
+    plot_width: f64,
+    /// Plotter paper height in mm
+    #[arg(long, default_value_t = GCode::default().height_mm)]
+    plot_height: f64,
+    /// G-code command to lift the pen
+    #[arg(long, default_value_t = GCode::default().pen_up)]
+    pen_up: String,
+    /// G-code command to lower the pen
+    #[arg(long, default_value_t = GCode::default().pen_down)]
+    pen_down: String,
+    /// G-code drawing speed in mm/min
+    #[arg(long, default_value_t = GCode::default().feed_rate)]
+    feed_rate: f64,
+    /// G-code pen up speed in mm/min
+    #[arg(long, default_value_t = GCode::default().travel_rate)]
+    travel_rate: f64,
+    /// HPGL pen number
+    #[arg(long, default_value_t = Hpgl::default().pen)]
+    pen: u8,
+}
+
+impl OutputArgs {
+    /// Write the drawing to `{name}.svg`, `{name}.png`, `{name}.gcode` or
+    /// `{name}.hpgl`. Plotter formats take their geometry from `drawing`
+    fn write(
+        &self,
+        name: &str,
+        svg: String,
+        drawing: &Svg,
+        map: &Map,
+    ) -> Result<(), Box<dyn Error>> {
+        let plot = || -> Result<_, Box<dyn Error>> {
+            let strokes = strokes(&drawing.shapes(map)?, drawing.end_radius as f64);
+            Ok((strokes, drawing.width as f64, drawing.height as f64))
+        };
+
+        match self.format {
+            Format::Svg => write(format!("{name}.svg"), svg)?,
+            Format::Png => {
+                // same limits as the web, so huge scales fail quickly
+                if !(0.1..=4.0).contains(&self.scale) {
+                    return Err("Scale must be between 0.1 and 4".into());
+                }
+                let size = match (self.dpi, self.png_width) {
+                    (Some(dpi), _) if !(10.0..=384.0).contains(&dpi) => {
+                        return Err("DPI must be between 10 and 384".into());
+                    }
+                    (Some(dpi), _) => RasterSize::Dpi(dpi),
+                    (None, Some(width)) => RasterSize::Width(width),
+                    (None, None) => RasterSize::Scale(self.scale),
+                };
+                write(format!("{name}.png"), to_png(&svg, size)?)?
+            }
+            Format::Gcode => {
+                let (strokes, width, height) = plot()?;
+                let gcode = GCode {
+                    pen_up: self.pen_up.clone(),
+                    pen_down: self.pen_down.clone(),
+                    feed_rate: self.feed_rate,
+                    travel_rate: self.travel_rate,
+                    width_mm: self.plot_width,
+                    height_mm: self.plot_height,
+                };
+                write(
+                    format!("{name}.gcode"),
+                    gcode.export(&strokes, width, height),
+                )?
+            }
+            Format::Hpgl => {
+                let (strokes, width, height) = plot()?;
+                let hpgl = Hpgl {
+                    pen: self.pen,
+                    width_mm: self.plot_width,
+                    height_mm: self.plot_height,
+                };
+                write(format!("{name}.hpgl"), hpgl.export(&strokes, width, height))?
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Parser)]
 struct GenerateArgs {
     #[arg(short, long)]
     seed: String,
+    /// How the canvas is split into leaves: quadtree, bsp (cut in two at
+    /// random), kd (cut around random points) or voronoi (irregular cells)
+    #[arg(long, default_value_t = Partition::default())]
+    partition: Partition,
+    /// Chance of a quadtree or BSP leaf splitting, past the forced depth
+    #[arg(long, default_value_t = SplitRules::default().probability)]
+    split_probability: f64,
+    /// Leaves this deep or shallower always split
+    #[arg(long, default_value_t = SplitRules::default().forced_depth)]
+    forced_split_depth: usize,
+    /// Leaves this deep never split
+    #[arg(long)]
+    max_depth: Option<usize>,
     #[arg(short, long, default_value_t = 3)]
     min_leaf_size: usize,
+    /// Width and height of a square canvas
     #[arg(short, long, default_value_t = 45)]
     canvas_size: usize,
+    /// Overrides canvas size
+    #[arg(long)]
+    width: Option<usize>,
+    /// Overrides canvas size
+    #[arg(long)]
+    height: Option<usize>,
     #[arg(short, long, default_value_t = 2)]
     density: u8,
+    /// Where trails start: per-leaf (random cells in each leaf),
+    /// poisson[:<min spacing>], jittered (spread over a grid in each leaf),
+    /// noise[:<blob size>] or points:<x,y cells separated by spaces>
+    #[arg(long, default_value_t = Placement::default())]
+    placement: Placement,
+    /// Start more trails where a greyscale PGM image is darker
+    #[arg(long, conflicts_with = "placement")]
+    placement_image: Option<String>,
+    /// Other cells of the same leaf to try when a start can't grow a trail
+    #[arg(long, default_value_t = 0)]
+    retries: usize,
+    /// Keep adding starts in random leaves until this many trails are
+    /// planted, up to 100000
+    #[arg(long, value_parser = RangedU64ValueParser::<usize>::new().range(1..=100_000))]
+    target_trails: Option<usize>,
+    /// Most starts to try, including retries, up to 1000000. Defaults to ten
+    /// per target trail
+    #[arg(long, value_parser = RangedU64ValueParser::<usize>::new().range(1..=1_000_000))]
+    attempt_budget: Option<usize>,
+    #[arg(short, long, default_value_t = DEFAULT_TRAIL_LENGTH)]
+    trail_length: usize,
+    /// How heights are written: digits, base36 or separated. Defaults to
+    /// the smallest which fits the trail length
+    #[arg(short, long)]
+    alphabet: Option<Alphabet>,
+    /// Cells trails step to: von-neumann (up, down, left and right), moore
+    /// (diagonals as well) or hex (six sides, drawn as a honeycomb)
+    #[arg(long, default_value_t = Neighbourhood::default())]
+    neighbourhood: Neighbourhood,
+    /// Let trails cross the edges so the image tiles seamlessly
+    #[arg(long)]
+    wrap: bool,
+    /// Keep trails inside a shape: circle, ring, ring:<inner size from 0 to
+    /// 1> or polygon:<x,y corners from 0 to 1, separated by spaces>
+    #[arg(long)]
+    mask: Option<Shape>,
+    /// Keep trails inside a text file of `#` for usable cells and `.` for
+    /// unusable ones, stretched to the canvas
+    #[arg(long, conflicts_with = "mask")]
+    mask_file: Option<String>,
+    /// Heights between trails: random, safe (never completes an extra
+    /// trail) or near-miss (safe, with climbs that stop one step short)
+    #[arg(long, default_value_t = FillStrategy::default())]
+    fill: FillStrategy,
+    #[command(flatten)]
+    selection: SelectionArgs,
+    #[command(flatten)]
+    style: StyleArgs,
+    #[command(flatten)]
+    output: OutputArgs,
 }
 
+/// Map files keep drawing with twice the tile size and offset of
+/// generated images
 #[derive(Parser)]
+#[command(
+    mut_arg("tile_size", |arg| arg.default_value("64")),
+    mut_arg("offset", |arg| arg.default_value("32"))
+)]
 struct FromFileArgs {
     #[arg(short, long)]
     name: String,
+    #[arg(short, long, default_value_t = DEFAULT_TRAIL_LENGTH)]
+    trail_length: usize,
+    /// How heights are written: digits, base36 or separated. Defaults to
+    /// the smallest which fits the trail length
+    #[arg(short, long)]
+    alphabet: Option<Alphabet>,
+    /// Cells trails step to: von-neumann (up, down, left and right), moore
+    /// (diagonals as well) or hex (six sides, drawn as a honeycomb)
+    #[arg(long, default_value_t = Neighbourhood::default())]
+    neighbourhood: Neighbourhood,
+    /// Let trails cross the edges so the image tiles seamlessly
+    #[arg(long)]
+    wrap: bool,
+    #[command(flatten)]
+    selection: SelectionArgs,
+    #[command(flatten)]
+    style: StyleArgs,
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+#[derive(Parser)]
+struct StatsArgs {
+    #[arg(short, long)]
+    name: String,
+    #[arg(short, long, default_value_t = DEFAULT_TRAIL_LENGTH)]
+    trail_length: usize,
+    /// How heights are written: digits, base36 or separated. Defaults to
+    /// the smallest which fits the trail length
+    #[arg(short, long)]
+    alphabet: Option<Alphabet>,
+    /// Cells trails step to: von-neumann (up, down, left and right), moore
+    /// (diagonals as well) or hex (six sides, drawn as a honeycomb)
+    #[arg(long, default_value_t = Neighbourhood::default())]
+    neighbourhood: Neighbourhood,
+    /// Let trails cross the edges so the image tiles seamlessly
+    #[arg(long)]
+    wrap: bool,
 }
 
-fn main() -> Result<(), io::Error> {
+fn main() -> ExitCode {
     let args = Args::parse();
 
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Pen up travel saved by reordering trails
+fn report(order: &PathOrder, ordering: PathOrdering) {
+    if ordering != PathOrdering::Scan {
+        println!(
+            "Pen up travel: {:.1} -> {:.1} tiles",
+            order.before, order.after
+        );
+    }
+}
+
+fn run(args: &Args) -> Result<(), Box<dyn Error>> {
     match &args.command {
         Commands::Generate(args) => {
-            let output = trails::create(
-                &args.seed,
-                args.canvas_size,
-                args.min_leaf_size,
-                args.density,
+            let mut builder = GenerationConfig::builder(&args.seed)
+                .width(args.width.unwrap_or(args.canvas_size))
+                .height(args.height.unwrap_or(args.canvas_size))
+                .partition(args.partition)
+                .split_probability(args.split_probability)
+                .forced_split_depth(args.forced_split_depth)
+                .min_leaf_size(args.min_leaf_size)
+                .density(args.density)
+                .placement(args.placement.clone())
+                .retries(args.retries)
+                .trail_length(args.trail_length)
+                .neighbourhood(args.neighbourhood)
+                .wrap(args.wrap)
+                .fill(args.fill)
+                .selection(args.selection.selection)
+                .style(Style::from(&args.style));
+            if let Some(alphabet) = args.alphabet {
+                builder = builder.alphabet(alphabet);
+            }
+            if let Some(max_trails) = args.selection.max_trails {
+                builder = builder.max_trails(max_trails);
+            }
+            if let Some(max_depth) = args.max_depth {
+                builder = builder.max_depth(max_depth);
+            }
+            if let Some(target) = args.target_trails {
+                builder = builder.target_trails(target);
+            }
+            if let Some(budget) = args.attempt_budget {
+                builder = builder.attempt_budget(budget);
+            }
+            if let Some(mask) = &args.mask {
+                builder = builder.mask(mask.clone());
+            }
+            if let Some(filename) = &args.mask_file {
+                builder = builder.mask(Shape::grid(&read_to_string(filename)?)?);
+            }
+            if let Some(filename) = &args.placement_image {
+                let image = Greyscale::pgm(&read(filename)?)?;
+                builder = builder.placement(Placement::Image(image));
+            }
+            let config = builder.build()?;
+            let output = trails::generate(&config)?;
+            report(&output.order, config.style.ordering);
+            println!(
+                "Planted {} trails from {} starting points ({} starts tried)",
+                output.growth.planted(),
+                output.growth.requested,
+                output.growth.starts.len()
             );
 
-            write(format!("./trail-{}.svg", args.seed), output)?;
+            let drawing = Svg::from_style(&config.style, output.map.width, output.map.height)
+                .with_neighbourhood(output.map.neighbourhood)
+                .with_wrap(output.map.wrap)
+                .with_order(output.order.order);
+            args.output.write(
+                &format!("./trail-{}", args.seed),
+                output.svg,
+                &drawing,
+                &output.map,
+            )?;
 
             Ok(())
         }
         Commands::FromFile(args) => {
             let filename = &args.name;
             let input = read_to_string(filename)?;
-            let mut map = Map::parse(&input);
+            let alphabet = args
+                .alphabet
+                .unwrap_or(Alphabet::for_trail_length(args.trail_length));
+            validate_trails(
+                args.trail_length,
+                alphabet,
+                args.selection.max_trails,
+                args.wrap,
+                args.neighbourhood,
+            )?;
+            let mut map = Map::parse_with(&input, alphabet)?
+                .with_neighbourhood(args.neighbourhood)
+                .with_wrap(args.wrap);
+
+            map.paths = select_paths(
+                &map,
+                args.trail_length,
+                args.selection.selection,
+                args.selection.max_trails,
+                filename,
+            )?;
 
-            map.find_all_paths();
+            let style = Style::from(&args.style);
+            let trail_colours = match &style.palette {
+                Some(palette) => palette.trail_colours(&map, style.colour_strategy, None, filename),
+                None => vec![],
+            };
+            let svg = Svg::from_style(&style, map.width, map.height)
+                .with_neighbourhood(map.neighbourhood)
+                .with_wrap(map.wrap)
+                .with_trail_colours(trail_colours);
+            let order = svg.path_order(&map, style.ordering);
+            report(&order, style.ordering);
 
-            let svg = Svg::new(64, 32, map.width, map.height, 2, "black", 10);
-            let output = svg.draw(&map);
+            let svg = svg.with_order(order.order);
+            let output = svg.draw(&map)?;
 
-            write("./trail.svg", output)?;
+            args.output.write("./trail", output, &svg, &map)?;
+            Ok(())
+        }
+        Commands::Stats(args) => {
+            let input = read_to_string(&args.name)?;
+            let alphabet = args
+                .alphabet
+                .unwrap_or(Alphabet::for_trail_length(args.trail_length));
+            validate_trails(
+                args.trail_length,
+                alphabet,
+                None,
+                args.wrap,
+                args.neighbourhood,
+            )?;
+            let map = Map::parse_with(&input, alphabet)?
+                .with_neighbourhood(args.neighbourhood)
+                .with_wrap(args.wrap);
+
+            let scores = map.scores(args.trail_length);
+            let ratings = map.ratings(args.trail_length);
+            println!("trailhead\tscore\trating");
+            for ((pos, score), rating) in map.trailheads.iter().zip(&scores).zip(&ratings) {
+                println!("{},{}\t{score}\t{rating}", pos.x, pos.y);
+            }
+            println!(
+                "total\t{}\t{}",
+                scores.iter().sum::<usize>(),
+                ratings
+                    .iter()
+                    .fold(0u64, |total, rating| total.saturating_add(*rating))
+            );
             Ok(())
         }
     }
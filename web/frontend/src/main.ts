@@ -15,11 +15,12 @@ function setLoading(isLoading: boolean) {
   }
 }
 
-function setError(hasError: boolean) {
+function setError(hasError: boolean, message = 'Please try again.') {
   const errorMsg = document.querySelector('#error')! as HTMLElement;
 
   if (hasError) {
     // show error msg
+    errorMsg.querySelector('p')!.textContent = message;
     errorMsg.classList.add('hasError');
   } else {
     errorMsg.classList.remove('hasError');
@@ -28,9 +29,27 @@ function setError(hasError: boolean) {
 
 const defaultOptions = {
   seed: String(Date.now()),
+  partition: 'quadtree',
+  splitProbability: 0.85,
   minLeafSize: 3,
-  canvasSize: 40,
+  width: 40,
+  height: 40,
   density: 2,
+  placement: 'per-leaf',
+  retries: 0,
+  targetTrails: undefined as number | undefined,
+  neighbourhood: 'von-neumann',
+  wrap: false,
+  mask: undefined as string | undefined,
+  fill: 'random',
+  palette: 'mono',
+  colourStrategy: 'trailhead',
+  pathStyle: 'square',
+  ordering: 'scan',
+  merge: false,
+  debugOverlay: false,
+  selection: 'all',
+  maxTrails: undefined as number | undefined,
 };
 let cache = defaultOptions;
 async function handleForm(e: SubmitEvent) {
@@ -42,8 +61,17 @@ async function handleForm(e: SubmitEvent) {
 
   const parsed = { ...defaultOptions, ...Object.fromEntries(data) };
   parsed.minLeafSize = Number(parsed.minLeafSize);
-  parsed.canvasSize = Number(parsed.canvasSize);
+  parsed.width = Number(parsed.width);
+  parsed.height = Number(parsed.height);
   parsed.density = Number(parsed.density);
+  parsed.splitProbability = Number(parsed.splitProbability);
+  parsed.retries = Number(parsed.retries);
+  parsed.targetTrails = parsed.targetTrails ? Number(parsed.targetTrails) : undefined;
+  parsed.merge = data.has('merge');
+  parsed.debugOverlay = data.has('debugOverlay');
+  parsed.wrap = data.has('wrap');
+  parsed.mask = parsed.mask || undefined;
+  parsed.maxTrails = parsed.maxTrails ? Number(parsed.maxTrails) : undefined;
 
   if (JSON.stringify(parsed) !== JSON.stringify(cache)) {
     cache = parsed;
@@ -76,13 +104,47 @@ function handleDownload() {
   const svgUrl = URL.createObjectURL(svgBlob);
   const link = document.createElement('a');
   link.href = svgUrl;
-  link.download = `${cache.seed}-${cache.density}-${cache.canvasSize}-${cache.minLeafSize}.svg`;
+  link.download = `${cache.seed}-${cache.density}-${cache.width}x${cache.height}-${cache.minLeafSize}.svg`;
   link.click();
 }
 
+// double size for sharper images. The server shrinks canvases which would be
+// too large at this scale, so every size the form allows downloads
+const PNG_SCALE = 2;
+
+async function handleDownloadPng() {
+  setError(false);
+
+  try {
+    const response = await fetch(`${import.meta.env.VITE_BASE_URL}/api/generate?format=png&scale=${PNG_SCALE}`, {
+      method: 'post',
+      headers: {
+        'Content-Type': 'application/json',
+      },
+      body: JSON.stringify(cache),
+    });
+    if (!response.ok) {
+      // validation errors come back as `{ message }`
+      const { message } = await response.json().catch(() => ({}));
+      throw new Error(message);
+    }
+
+    const pngUrl = URL.createObjectURL(await response.blob());
+    const link = document.createElement('a');
+    link.href = pngUrl;
+    link.download = `${cache.seed}-${cache.density}-${cache.width}x${cache.height}-${cache.minLeafSize}.png`;
+    link.click();
+  } catch (err) {
+    console.error(err);
+    setError(true, err instanceof Error && err.message ? err.message : undefined);
+  }
+}
+
 document.addEventListener('DOMContentLoaded', function ready() {
   const form = document.querySelector('#options');
   (form as HTMLFormElement)?.addEventListener('submit', handleForm);
   const downloadLink = document.querySelector('#download');
   downloadLink?.addEventListener('click', handleDownload);
+  const downloadPngLink = document.querySelector('#download-png');
+  downloadPngLink?.addEventListener('click', handleDownloadPng);
 });
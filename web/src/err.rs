@@ -1,5 +1,6 @@
 use axum::{extract::rejection::JsonRejection, http::StatusCode, response::IntoResponse};
 use serde_derive::Serialize;
+use trails::error::Error;
 
 use crate::extractors::AppJson;
 
@@ -9,9 +10,18 @@ impl From<JsonRejection> for AppError {
     }
 }
 
+impl From<Error> for AppError {
+    fn from(value: Error) -> Self {
+        Self::Trails(value)
+    }
+}
+
 // Handle errors
 pub enum AppError {
     JsonRejection(JsonRejection),
+    Trails(Error),
+    /// Request is outside the limits for the web
+    Validation(String),
 }
 
 impl IntoResponse for AppError {
@@ -28,6 +38,11 @@ impl IntoResponse for AppError {
                 tracing::error!(%err, "Err parsing JSON input");
                 (err.status(), err.body_text())
             }
+            AppError::Trails(err) => {
+                tracing::error!(%err, "Failed to generate");
+                (StatusCode::BAD_REQUEST, err.to_string())
+            }
+            AppError::Validation(message) => (StatusCode::BAD_REQUEST, message),
         };
 
         (StatusCode::BAD_REQUEST, AppJson(ErrorResponse { message })).into_response()
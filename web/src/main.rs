@@ -1,5 +1,6 @@
 use axum::{
     Router,
+    extract::Query,
     http::{
         HeaderMap, Method, StatusCode,
         header::{self, CONTENT_TYPE},
@@ -19,11 +20,29 @@ use tower_http::{
 };
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+use err::AppError;
 use extractors::AppJson;
+use trails::{
+    config::{DEFAULT_TRAIL_LENGTH, GenerationConfig, Style},
+    curve::PathStyle,
+    fill::FillStrategy,
+    mask::Shape,
+    order::PathOrdering,
+    palette::{ColourStrategy, Palette},
+    partition::Partition,
+    placement::Placement,
+    raster::{RasterSize, to_png},
+    select::Selection,
+    topology::Neighbourhood,
+};
 
 mod err;
 mod extractors;
 
+/// Largest PNG the web will render, 16 megapixels. Bigger canvases are
+/// scaled down to fit rather than rejected
+const MAX_PNG_PIXELS: u64 = 16_000_000;
+
 async fn home() -> impl IntoResponse {
     let template_path = env::var("TEMPLATE_PATH").unwrap_or(String::from("./frontend/dist"));
     let mut app_env = Environment::new();
@@ -39,14 +58,21 @@ async fn home() -> impl IntoResponse {
         .as_millis();
     let template = app_env.get_template("home").expect("Couldn't get template");
 
-    let density = 2;
-    let canvas_size = 45;
-    let leaf_size = 3;
-    let image = trails::create(&now.to_string(), canvas_size, leaf_size, density);
+    let config = GenerationConfig::builder(&now.to_string())
+        .build()
+        .expect("Default config is invalid");
+    let image = trails::generate(&config).expect("Failed to generate").svg;
 
     Html(
         template
-            .render(context! { seed => now, image => image, density => 2, size => 45, leaf => 3 })
+            .render(context! {
+                seed => now,
+                image => image,
+                density => config.density,
+                width => config.width,
+                height => config.height,
+                leaf => config.min_leaf_size
+            })
             .expect("Failed to render"),
     )
 }
@@ -55,30 +81,261 @@ async fn home() -> impl IntoResponse {
 #[serde(rename_all = "camelCase")]
 struct Payload {
     seed: String,
-    canvas_size: usize,
+    // square canvas, kept for older clients. `width` and `height` take precedence
+    canvas_size: Option<usize>,
+    width: Option<usize>,
+    height: Option<usize>,
+    partition: Option<String>,
+    split_probability: Option<f64>,
+    max_depth: Option<usize>,
     min_leaf_size: usize,
     density: u8,
+    placement: Option<String>,
+    retries: Option<usize>,
+    target_trails: Option<usize>,
+    trail_length: Option<usize>,
+    neighbourhood: Option<String>,
+    wrap: Option<bool>,
+    mask: Option<String>,
+    fill: Option<String>,
+    palette: Option<String>,
+    colour_strategy: Option<String>,
+    path_style: Option<String>,
+    corner_radius: Option<usize>,
+    ordering: Option<String>,
+    merge: Option<bool>,
+    debug_overlay: Option<bool>,
+    selection: Option<String>,
+    max_trails: Option<usize>,
 }
 
-async fn generate(AppJson(payload): AppJson<Payload>) -> impl IntoResponse {
-    // validation
-    if payload.canvas_size > 200 || payload.min_leaf_size > 10 || payload.density > 15 {
-        return StatusCode::BAD_REQUEST.into_response();
+impl Payload {
+    fn dimensions(&self) -> Option<(usize, usize)> {
+        let width = self.width.or(self.canvas_size)?;
+        let height = self.height.or(self.canvas_size)?;
+        Some((width, height))
     }
 
-    let image = trails::create(
-        &payload.seed,
-        payload.canvas_size,
-        payload.min_leaf_size,
-        payload.density,
-    );
+    /// Check the limits for the web and build the generation config
+    fn config(&self) -> Result<GenerationConfig, AppError> {
+        let invalid = |message: &str| AppError::Validation(message.to_string());
+
+        let (width, height) = self
+            .dimensions()
+            .ok_or_else(|| invalid("Missing width and height"))?;
+        let trail_length = self.trail_length.unwrap_or(DEFAULT_TRAIL_LENGTH);
+        if width > 200
+            || height > 200
+            || self.min_leaf_size > 10
+            || self.density > 15
+            || trail_length > 50
+        {
+            return Err(invalid("Option out of range"));
+        }
+
+        // only built in palettes, custom colours aren't exposed to the web
+        let palette = match &self.palette {
+            Some(name) => Some(Palette::named(name).ok_or_else(|| invalid("Unknown palette"))?),
+            None => None,
+        };
+        let colour_strategy = match &self.colour_strategy {
+            Some(strategy) => strategy
+                .parse::<ColourStrategy>()
+                .map_err(AppError::Validation)?,
+            None => ColourStrategy::default(),
+        };
+        let path_style = match &self.path_style {
+            Some(style) => style.parse::<PathStyle>().map_err(AppError::Validation)?,
+            None => PathStyle::default(),
+        };
+        let ordering = match &self.ordering {
+            Some(ordering) => ordering
+                .parse::<PathOrdering>()
+                .map_err(AppError::Validation)?,
+            None => PathOrdering::default(),
+        };
+        let neighbourhood = match &self.neighbourhood {
+            Some(neighbourhood) => neighbourhood
+                .parse::<Neighbourhood>()
+                .map_err(AppError::Validation)?,
+            None => Neighbourhood::default(),
+        };
+        // polygons cost a check per point for every cell, so only the
+        // built in shapes are exposed to the web
+        let mask = match &self.mask {
+            Some(mask) => match mask.parse::<Shape>() {
+                Ok(Shape::Polygon(_)) => return Err(invalid("Unknown mask")),
+                mask => Some(mask.map_err(AppError::Validation)?),
+            },
+            None => None,
+        };
+        let partition = match &self.partition {
+            Some(partition) => partition
+                .parse::<Partition>()
+                .map_err(AppError::Validation)?,
+            None => Partition::default(),
+        };
+        // custom points aren't exposed to the web
+        let placement = match &self.placement {
+            Some(placement) => match placement.parse::<Placement>() {
+                Ok(Placement::Points(_)) => return Err(invalid("Unknown placement")),
+                placement => placement.map_err(AppError::Validation)?,
+            },
+            None => Placement::default(),
+        };
+        let fill = match &self.fill {
+            Some(fill) => fill.parse::<FillStrategy>().map_err(AppError::Validation)?,
+            None => FillStrategy::default(),
+        };
+        let selection = match &self.selection {
+            Some(selection) => selection
+                .parse::<Selection>()
+                .map_err(AppError::Validation)?,
+            None => Selection::default(),
+        };
+        let corner_radius = self.corner_radius.unwrap_or(Style::default().corner_radius);
+        if corner_radius > 32 {
+            return Err(invalid("Corner radius out of range"));
+        }
+
+        let retries = self.retries.unwrap_or_default();
+        if retries > 10 {
+            return Err(invalid("Retries out of range"));
+        }
+
+        let mut builder = GenerationConfig::builder(&self.seed);
+        if let Some(max_trails) = self.max_trails {
+            builder = builder.max_trails(max_trails);
+        }
+        if let Some(probability) = self.split_probability {
+            builder = builder.split_probability(probability);
+        }
+        if let Some(max_depth) = self.max_depth {
+            builder = builder.max_depth(max_depth);
+        }
+        if let Some(target) = self.target_trails {
+            if target > 1000 {
+                return Err(invalid("Target trails out of range"));
+            }
+            builder = builder.target_trails(target);
+        }
+        if let Some(mask) = mask {
+            builder = builder.mask(mask);
+        }
+        Ok(builder
+            .width(width)
+            .height(height)
+            .partition(partition)
+            .min_leaf_size(self.min_leaf_size)
+            .density(self.density)
+            .placement(placement)
+            .retries(retries)
+            .trail_length(trail_length)
+            .neighbourhood(neighbourhood)
+            .wrap(self.wrap.unwrap_or_default())
+            .fill(fill)
+            .selection(selection)
+            .style(Style {
+                palette,
+                colour_strategy,
+                path_style,
+                corner_radius,
+                ordering,
+                merge: self.merge.unwrap_or_default(),
+                debug_overlay: self.debug_overlay.unwrap_or_default(),
+                ..Style::default()
+            })
+            .build()?)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct OutputQuery {
+    format: Option<String>,
+    scale: Option<f32>,
+}
+
+impl OutputQuery {
+    /// `?format=png` or an `Accept: image/png` header
+    fn wants_png(&self, headers: &HeaderMap) -> bool {
+        match &self.format {
+            Some(format) => format == "png",
+            None => headers
+                .get(header::ACCEPT)
+                .and_then(|accept| accept.to_str().ok())
+                .is_some_and(|accept| accept.contains("image/png")),
+        }
+    }
+}
+
+async fn generate(
+    Query(query): Query<OutputQuery>,
+    request_headers: HeaderMap,
+    AppJson(payload): AppJson<Payload>,
+) -> Result<impl IntoResponse, AppError> {
+    let config = payload.config()?;
+    let output = trails::generate(&config)?;
+    let image = output.svg;
     let mut headers = HeaderMap::new();
+    // pen up travel in tiles, before and after reordering
+    headers.insert(
+        "x-travel-before",
+        format!("{:.1}", output.order.before)
+            .parse()
+            .expect("Failed to add travel header"),
+    );
+    headers.insert(
+        "x-travel-after",
+        format!("{:.1}", output.order.after)
+            .parse()
+            .expect("Failed to add travel header"),
+    );
+
+    // trails planted, and starting points asked for before retries
+    headers.insert(
+        "x-trails-planted",
+        output
+            .growth
+            .planted()
+            .to_string()
+            .parse()
+            .expect("Failed to add growth header"),
+    );
+    headers.insert(
+        "x-trails-requested",
+        output
+            .growth
+            .requested
+            .to_string()
+            .parse()
+            .expect("Failed to add growth header"),
+    );
+
+    if query.wants_png(&request_headers) {
+        let scale = query.scale.unwrap_or(1.0);
+        if !(0.1..=4.0).contains(&scale) {
+            return Err(AppError::Validation(String::from("Scale out of range")));
+        }
+        let png = to_png(
+            &image,
+            RasterSize::Fit {
+                scale,
+                max_pixels: MAX_PNG_PIXELS,
+            },
+        )?;
+        headers.insert(
+            header::CONTENT_TYPE,
+            "image/png".parse().expect("Failed to add png header"),
+        );
+        return Ok((headers, png).into_response());
+    }
+
     headers.insert(
         header::CONTENT_TYPE,
         "image/svg+xml".parse().expect("Failed to add svg header"),
     );
 
-    (headers, image).into_response()
+    Ok((headers, image).into_response())
 }
 
 async fn not_found() -> impl IntoResponse {
@@ -139,3 +396,82 @@ async fn main() {
         .await
         .expect("Failed to start server");
 }
+
+#[cfg(test)]
+mod test {
+    use axum::{
+        extract::Query,
+        http::{HeaderMap, StatusCode, header},
+        response::IntoResponse,
+    };
+    use serde_json::json;
+
+    use crate::{MAX_PNG_PIXELS, OutputQuery, Payload, extractors::AppJson, generate};
+
+    fn payload(mask: &str) -> Payload {
+        serde_json::from_value(json!({
+            "seed": "mask",
+            "width": 200,
+            "height": 200,
+            "minLeafSize": 5,
+            "density": 5,
+            "mask": mask,
+        }))
+        .expect("Failed to build payload")
+    }
+
+    #[test]
+    fn it_should_reject_polygon_masks() {
+        let points: Vec<String> = (0..10_000)
+            .map(|index| {
+                let angle = index as f64 / 10_000.0 * std::f64::consts::TAU;
+                format!("{},{}", 0.5 + angle.cos() / 2.0, 0.5 + angle.sin() / 2.0)
+            })
+            .collect();
+        let Err(err) = payload(&format!("polygon:{}", points.join(" "))).config() else {
+            panic!("Polygon mask should be rejected");
+        };
+        assert_eq!(err.into_response().status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn it_should_allow_built_in_masks() {
+        assert!(payload("circle").config().is_ok());
+        assert!(payload("ring:0.4").config().is_ok());
+    }
+
+    #[tokio::test]
+    async fn it_should_fit_large_pngs() {
+        for neighbourhood in ["von-neumann", "hex"] {
+            let payload: Payload = serde_json::from_value(json!({
+                "seed": "png",
+                "width": 200,
+                "height": 200,
+                "minLeafSize": 3,
+                "density": 2,
+                "neighbourhood": neighbourhood,
+            }))
+            .expect("Failed to build payload");
+            // as sent by the frontend's download button
+            let query = Query(OutputQuery {
+                format: Some(String::from("png")),
+                scale: Some(2.0),
+            });
+
+            let response = generate(query, HeaderMap::new(), AppJson(payload))
+                .await
+                .map_err(|_| ())
+                .expect("Failed to render")
+                .into_response();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.headers()[header::CONTENT_TYPE], "image/png");
+
+            let png = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .expect("Failed to read body");
+            let width = u32::from_be_bytes([png[16], png[17], png[18], png[19]]);
+            let height = u32::from_be_bytes([png[20], png[21], png[22], png[23]]);
+            assert!(u64::from(width) * u64::from(height) <= MAX_PNG_PIXELS);
+        }
+    }
+}